@@ -0,0 +1,245 @@
+use crate::evm::EVMChainManager;
+use crate::mayan::{analyze_leg, CrossChainLeg, CrossChainQuery, SOLANA_CHAIN};
+use crate::solana::SolanaChainManager;
+use anyhow::Result;
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+
+/// CCTP domain of Solana
+pub const SOLANA_DOMAIN: u32 = 5;
+
+/// Header size of a V1 message: version, domains, 8-byte nonce, sender, recipient, caller
+const MESSAGE_V1_HEADER_LEN: usize = 4 + 4 + 4 + 8 + 32 + 32 + 32;
+/// Header size of a V2 message: 32-byte nonce plus the two finality thresholds
+const MESSAGE_V2_HEADER_LEN: usize = 4 + 4 + 4 + 32 + 32 + 32 + 32 + 4 + 4;
+/// Burn message body: version, burn token, mint recipient, amount, sender
+const BURN_MESSAGE_V1_LEN: usize = 4 + 32 + 32 + 32 + 32;
+/// V2 adds the max fee, the executed fee and the expiration block before the hook data
+const BURN_MESSAGE_V2_LEN: usize = BURN_MESSAGE_V1_LEN + 32 + 32 + 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CctpTransferKind {
+    /// USDC burned on this chain for a mint on the destination domain
+    Burn,
+    /// USDC minted on this chain for a burn on the source domain
+    Mint,
+}
+
+/// One side of a CCTP transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CctpTransfer {
+    pub kind: CctpTransferKind,
+    /// CCTP protocol version, 1 or 2
+    pub version: u32,
+    pub source_domain: u32,
+    pub destination_domain: u32,
+    /// Decimal for V1, 32-byte hex for V2. `None` for V2 burns, whose nonce is assigned by
+    /// the attestation service
+    pub nonce: Option<String>,
+    /// Token burned on the source domain, as a 32-byte hex address
+    pub burn_token: Option<String>,
+    /// Token minted on this chain, mints only
+    pub mint_token: Option<String>,
+    /// Burned amount in the token's base units
+    pub amount: String,
+    /// Amount minted to the recipient after fees, mints only
+    pub minted_amount: Option<String>,
+    pub depositor: Option<String>,
+    pub mint_recipient: Option<String>,
+    /// Maximum fast transfer fee, V2 only
+    pub max_fee: Option<String>,
+    /// Fee taken on the destination, V2 mints only
+    pub fee_executed: Option<String>,
+}
+
+/// A burn paired with its mint on the destination chain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CctpTransferMatch {
+    pub source: CrossChainLeg,
+    /// `None` while the attestation has not been received on the destination
+    pub destination: Option<CrossChainLeg>,
+    pub burn: CctpTransfer,
+    pub mint: Option<CctpTransfer>,
+    pub amount_burned: String,
+    pub amount_minted: Option<String>,
+    /// Burned minus minted amount, in the token's base units
+    pub fee: Option<String>,
+}
+
+/// CCTP domain of a chain key
+pub fn domain(chain: &str) -> Option<u32> {
+    match chain {
+        "ethereum" => Some(0),
+        "avalanche" => Some(1),
+        "optimism" => Some(2),
+        "arbitrum" => Some(3),
+        "solana" | "mainnet" => Some(SOLANA_DOMAIN),
+        "base" => Some(6),
+        "polygon" => Some(7),
+        "unichain" => Some(10),
+        _ => None,
+    }
+}
+
+/// Chain key of a CCTP domain, `solana` for Solana mainnet
+pub fn domain_chain(domain: u32) -> Option<&'static str> {
+    match domain {
+        0 => Some("ethereum"),
+        1 => Some("avalanche"),
+        2 => Some("optimism"),
+        3 => Some("arbitrum"),
+        SOLANA_DOMAIN => Some(SOLANA_CHAIN),
+        6 => Some("base"),
+        7 => Some("polygon"),
+        10 => Some("unichain"),
+        _ => None,
+    }
+}
+
+/// Parse a CCTP V1 or V2 message carrying a burn message body, big-endian throughout
+pub(crate) fn parse_message(kind: CctpTransferKind, message: &[u8]) -> Option<CctpTransfer> {
+    let u32_at = |offset: usize| {
+        Some(u32::from_be_bytes(
+            message.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let (version, nonce, body) = match u32_at(0)? {
+        0 => (
+            1,
+            Some(u64::from_be_bytes(message.get(12..20)?.try_into().ok()?).to_string()),
+            message.get(MESSAGE_V1_HEADER_LEN..)?,
+        ),
+        1 => {
+            let nonce = message.get(12..44)?;
+            (
+                2,
+                nonce
+                    .iter()
+                    .any(|byte| *byte != 0)
+                    .then(|| format!("0x{}", hex::encode(nonce))),
+                message.get(MESSAGE_V2_HEADER_LEN..)?,
+            )
+        }
+        _ => return None,
+    };
+
+    let mut transfer = parse_burn_message(kind, body)?;
+    transfer.version = version;
+    transfer.source_domain = u32_at(4)?;
+    transfer.destination_domain = u32_at(8)?;
+    transfer.nonce = nonce;
+    Some(transfer)
+}
+
+/// Parse a burn message body, leaving the header fields empty
+pub(crate) fn parse_burn_message(kind: CctpTransferKind, body: &[u8]) -> Option<CctpTransfer> {
+    if body.len() < BURN_MESSAGE_V1_LEN {
+        return None;
+    }
+    let word = |offset: usize| hex::encode(&body[offset..offset + 32]);
+    let amount = |offset: usize| U256::from_big_endian(&body[offset..offset + 32]).to_string();
+    let v2 = body.len() >= BURN_MESSAGE_V2_LEN && body[..4] == [0, 0, 0, 1];
+
+    Some(CctpTransfer {
+        kind,
+        version: if v2 { 2 } else { 1 },
+        source_domain: 0,
+        destination_domain: 0,
+        nonce: None,
+        burn_token: Some(word(4)),
+        mint_token: None,
+        amount: amount(68),
+        minted_amount: None,
+        depositor: Some(word(100)),
+        mint_recipient: Some(word(36)),
+        max_fee: v2.then(|| amount(132)),
+        fee_executed: (v2 && kind == CctpTransferKind::Mint).then(|| amount(164)),
+    })
+}
+
+/// Analyze a burn transaction and pair it with its mint on the destination chain.
+///
+/// Without a destination transaction, mints of V1 burns on EVM chains are searched in recent
+/// logs; other mints must be given explicitly.
+pub async fn match_transfer(
+    evm: &EVMChainManager,
+    solana: &SolanaChainManager,
+    query: &CrossChainQuery,
+) -> Result<CctpTransferMatch> {
+    let (source, _) = analyze_leg(evm, solana, &query.source_chain, &query.source_tx).await?;
+    let burn = leg_transfers(&source)
+        .iter()
+        .find(|transfer| transfer.kind == CctpTransferKind::Burn)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No CCTP burn in {}", query.source_tx))?;
+
+    let destination_chain = query
+        .destination_chain
+        .clone()
+        .or_else(|| domain_chain(burn.destination_domain).map(str::to_string))
+        .ok_or_else(|| anyhow::anyhow!("Unknown CCTP domain: {}", burn.destination_domain))?;
+    let destination_tx = match &query.destination_tx {
+        Some(tx) => Some(tx.clone()),
+        None if destination_chain != SOLANA_CHAIN => {
+            evm.find_cctp_mint(&destination_chain, &burn).await?
+        }
+        None => None,
+    };
+    let destination = match destination_tx {
+        Some(tx) => Some(analyze_leg(evm, solana, &destination_chain, &tx).await?.0),
+        None => None,
+    };
+
+    let mint = destination.as_ref().and_then(|leg| {
+        leg_transfers(leg)
+            .iter()
+            .find(|transfer| transfer.kind == CctpTransferKind::Mint && is_mint_of(transfer, &burn))
+            .cloned()
+    });
+    let amount_minted = mint.as_ref().map(|mint| {
+        mint.minted_amount
+            .clone()
+            .unwrap_or_else(|| mint.amount.clone())
+    });
+    let fee = amount_minted.as_ref().and_then(|minted| {
+        let burned = U256::from_dec_str(&burn.amount).ok()?;
+        let minted = U256::from_dec_str(minted).ok()?;
+        Some(burned.saturating_sub(minted).to_string())
+    });
+
+    Ok(CctpTransferMatch {
+        amount_burned: burn.amount.clone(),
+        source,
+        destination,
+        burn,
+        mint,
+        amount_minted,
+        fee,
+    })
+}
+
+/// Match on the nonce when the burn knows it, on the burn message otherwise
+fn is_mint_of(mint: &CctpTransfer, burn: &CctpTransfer) -> bool {
+    if mint.source_domain != burn.source_domain {
+        return false;
+    }
+    match &burn.nonce {
+        Some(nonce) => mint.nonce.as_ref() == Some(nonce),
+        None => {
+            mint.amount == burn.amount
+                && mint.mint_recipient == burn.mint_recipient
+                && mint.depositor == burn.depositor
+        }
+    }
+}
+
+fn leg_transfers(leg: &CrossChainLeg) -> &[CctpTransfer] {
+    if let Some(analysis) = &leg.evm {
+        &analysis.cctp_transfers
+    } else if let Some(analysis) = &leg.solana {
+        &analysis.cctp_transfers
+    } else {
+        &[]
+    }
+}
@@ -1,9 +1,33 @@
 use ethers::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{collections::HashMap, str::FromStr, sync::Arc};
 use anyhow::Result;
 use clap::Args;
 
+mod approvals;
+mod authorizations;
+mod balances;
+mod cctp;
+mod relay;
+mod state_diff;
+mod swaps;
+mod user_operations;
+mod wormhole;
+
+pub use approvals::{EVMApproval, EVMApprovalKind};
+pub use authorizations::{EVMAuthorization, EVMDelegation};
+pub use balances::EVMBalanceSource;
+pub use relay::{EVMGasAttribution, EVMNativeReimbursement};
+pub use state_diff::{EVMAccountDiff, EVMStateDiff, EVMStorageChange, EVMTokenSlotChange};
+pub use swaps::EVMSwap;
+pub use user_operations::EVMUserOperation;
+use crate::cctp::CctpTransfer;
+use crate::ledger::BalanceChange;
+use crate::wormhole::WormholeMessage;
+
+/// ERC20 Transfer event signature: Transfer(address,address,uint256)
+const TRANSFER_EVENT_SIGNATURE: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
 /// Configuration for EVM chains
 #[derive(Debug, Clone, Args)]
 pub struct EVMConfig {
@@ -34,6 +58,26 @@ pub struct EVMConfig {
     /// Ethereum RPC URL
     #[arg(long, env = "ETH_RPC_URL", default_value = "https://eth.llamarpc.com")]
     pub eth_rpc_url: String,
+
+    /// Guardian or Wormholescan API used to look up the signed VAA of published Wormhole messages
+    #[arg(long, env = "WORMHOLE_VAA_API_URL")]
+    pub wormhole_vaa_api_url: Option<String>,
+
+    /// Blocks searched back from the latest block when looking for the mint of a CCTP burn
+    #[arg(long, env = "CCTP_SEARCH_BLOCKS", default_value = "10000")]
+    pub cctp_search_blocks: u64,
+
+    /// Addresses of our own relayers, whose transactions are attributed to the relayed user (comma separated)
+    #[arg(long, env = "RELAYER_ADDRESSES", value_delimiter = ',')]
+    pub relayer_addresses: Vec<String>,
+
+    /// Trace internal value transfers with `debug_traceTransaction` for the balance ledger
+    #[arg(long, env = "TRACE_INTERNAL_TRANSFERS")]
+    pub trace_internal_transfers: bool,
+
+    /// Trace balance, nonce and storage changes with `prestateTracer` in diff mode
+    #[arg(long, env = "STATE_DIFF")]
+    pub state_diff: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,13 +98,49 @@ pub struct TransactionAnalysis {
     pub gas_limit: U256,
     pub transaction_fee: Option<U256>,
     pub erc20_transfers: Vec<ERC20Transfer>,
+    /// Uniswap V2, V3 and V4 style swap hops in log order
+    pub swaps: Vec<EVMSwap>,
+    /// Wormhole messages published by the transaction and VAAs it redeemed
+    pub wormhole_messages: Vec<WormholeMessage>,
+    /// Circle CCTP burns and mints
+    pub cctp_transfers: Vec<CctpTransfer>,
+    /// ERC20 and Permit2 allowances and EIP-2612 permits granted by the transaction
+    pub approvals: Vec<EVMApproval>,
+    /// ERC-4337 user operations, with the gas each smart account or paymaster was charged
+    pub user_operations: Vec<EVMUserOperation>,
+    /// EIP-7702 authorization list of set-code transactions
+    pub authorizations: Vec<EVMAuthorization>,
+    /// Upper bound of the intrinsic gas of the authorization list. Tuples whose authority
+    /// already exists are refunded part of it, which needs the chain state
+    pub max_authorization_gas: Option<u64>,
+    /// EOAs delegated to contract code by the authorizations
+    pub delegations: Vec<EVMDelegation>,
+    /// Gas payer and beneficiary of relayed and sponsored transactions
+    pub gas_attribution: EVMGasAttribution,
+    /// Net native and token change of every address, gas included
+    pub balance_ledger: Vec<BalanceChange>,
+    pub balance_ledger_source: EVMBalanceSource,
+    /// Account and storage changes, when `state_diff` is enabled and the RPC supports tracing
+    pub state_diff: Option<EVMStateDiff>,
     pub transaction_status: String,
     pub block_number: Option<U64>,
+    /// Unix timestamp of the block, when the block is known
+    pub block_timestamp: Option<U256>,
+    /// EIP-1559 base fee of the block
+    pub base_fee_per_gas: Option<U256>,
     pub from_address: String,
     pub to_address: Option<String>,
     pub value: U256,
 }
 
+/// Raw RPC data of a transaction, as fetched or as loaded from archived JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EVMTransactionData {
+    pub transaction: Transaction,
+    pub receipt: Option<TransactionReceipt>,
+    pub block: Option<Block<H256>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ERC20Transfer {
     pub token_address: String,
@@ -72,6 +152,12 @@ pub struct ERC20Transfer {
 pub struct EVMChainManager {
     chains: HashMap<String, ChainConfig>,
     providers: HashMap<String, Provider<Http>>,
+    http_client: reqwest::Client,
+    wormhole_vaa_api_url: Option<String>,
+    cctp_search_blocks: u64,
+    relayer_addresses: Vec<String>,
+    trace_internal_transfers: bool,
+    state_diff: bool,
 }
 
 impl EVMChainManager {
@@ -149,31 +235,117 @@ impl EVMChainManager {
             }
         }
 
-        EVMChainManager { chains, providers }
+        EVMChainManager {
+            chains,
+            providers,
+            http_client: reqwest::Client::new(),
+            wormhole_vaa_api_url: config.wormhole_vaa_api_url.clone(),
+            cctp_search_blocks: config.cctp_search_blocks,
+            relayer_addresses: config
+                .relayer_addresses
+                .iter()
+                .map(|address| address.to_lowercase())
+                .collect(),
+            trace_internal_transfers: config.trace_internal_transfers,
+            state_diff: config.state_diff,
+        }
     }
     
     pub fn get_supported_chains(&self) -> Vec<&ChainConfig> {
         self.chains.values().collect()
     }
+
+    pub fn get_chain(&self, chain_name: &str) -> Option<&ChainConfig> {
+        self.chains.get(chain_name)
+    }
     
     pub async fn analyze_transaction(&self, chain_name: &str, tx_hash: &str) -> Result<TransactionAnalysis> {
+        let data = self.fetch_transaction_data(chain_name, tx_hash).await?;
+        let mut analysis = self.analyze_transaction_data(chain_name, &data.transaction, data.receipt.as_ref(), data.block.as_ref())?;
+
+        // Token decimals for swap prices, message fees and signed VAAs of Wormhole messages
+        // need network access
+        if let Some(provider) = self.providers.get(chain_name) {
+            self.fetch_swap_prices(provider, analysis.block_number, &mut analysis.swaps).await;
+            if let Err(e) = self.fetch_wormhole_message_fees(provider, analysis.block_number, &mut analysis.wormhole_messages).await {
+                eprintln!("Error fetching Wormhole message fee: {}", e);
+            }
+        }
+        if let Some(api_url) = &self.wormhole_vaa_api_url {
+            crate::wormhole::fetch_signed_vaas(&self.http_client, api_url, &mut analysis.wormhole_messages).await;
+        }
+
+        // Internal transfers are only visible in a trace, which not every RPC supports
+        if let (true, Some(provider)) = (self.trace_internal_transfers, self.providers.get(chain_name)) {
+            match self.trace_internal_transfers(provider, data.transaction.hash).await {
+                Ok(transfers) => {
+                    analysis.balance_ledger = self.balance_ledger(&data.transaction, data.receipt.as_ref(), analysis.transaction_fee, Some(&transfers));
+                    analysis.balance_ledger_source = EVMBalanceSource::CallTracer;
+                    // Relayers may also be paid back in native value by internal calls
+                    analysis.gas_attribution = self.extract_gas_attribution(&data.transaction, data.receipt.as_ref(), &analysis.user_operations, Some(&transfers));
+                }
+                Err(e) => eprintln!("Error tracing internal transfers: {}", e),
+            }
+        }
+
+        // The prestate diff has the exact native changes, fees to the block producer included
+        if let (true, Some(provider)) = (self.state_diff, self.providers.get(chain_name)) {
+            let mut holders = vec![data.transaction.from];
+            holders.extend(data.transaction.to);
+            holders.extend(analysis.balance_ledger.iter().filter_map(|change| change.address.parse::<H160>().ok()));
+            match self.fetch_state_diff(provider, data.transaction.hash, &holders).await {
+                Ok(state_diff) => {
+                    analysis.balance_ledger = self.state_diff_ledger(data.receipt.as_ref(), &state_diff);
+                    analysis.balance_ledger_source = EVMBalanceSource::PrestateTracer;
+                    analysis.state_diff = Some(state_diff);
+                }
+                Err(e) => eprintln!("Error tracing state diff: {}", e),
+            }
+        }
+
+        Ok(analysis)
+    }
+
+    /// Fetch the transaction, its receipt and its block from the chain's RPC
+    pub async fn fetch_transaction_data(&self, chain_name: &str, tx_hash: &str) -> Result<EVMTransactionData> {
         let provider = Arc::new(self.providers.get(chain_name)
             .ok_or_else(|| anyhow::anyhow!("Chain not supported: {}", chain_name))?.clone());
         
-        let chain_config = self.chains.get(chain_name)
-            .ok_or_else(|| anyhow::anyhow!("Chain config not found: {}", chain_name))?;
-        
         // Parse transaction hash
         let tx_hash_bytes: H256 = tx_hash.parse()?;
         
         // Get transaction details
-        let tx = provider.get_transaction(tx_hash_bytes).await?
+        let transaction = provider.get_transaction(tx_hash_bytes).await?
             .ok_or_else(|| anyhow::anyhow!("Transaction not found: {}", tx_hash))?;
         
         // Get transaction receipt for gas usage and status
         let receipt = provider.get_transaction_receipt(tx_hash_bytes).await?;
 
-        let (gas_used, transaction_status, block_number) = if let Some(receipt) = &receipt {
+        // Get the block for its timestamp and base fee
+        let block = match transaction.block_hash {
+            Some(block_hash) => provider.get_block(block_hash).await?,
+            None => None,
+        };
+
+        Ok(EVMTransactionData { transaction, receipt, block })
+    }
+
+    /// Analyze a transaction from already fetched data, without network access.
+    /// A missing receipt means the transaction is still pending.
+    pub fn analyze_transaction_data(
+        &self,
+        chain_name: &str,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+        block: Option<&Block<H256>>,
+    ) -> Result<TransactionAnalysis> {
+        // Offline data may come from chains without a configured RPC
+        let chain_key = chain_name;
+        let chain_name = self.chains.get(chain_name)
+            .map(|chain_config| chain_config.name.clone())
+            .unwrap_or_else(|| chain_name.to_string());
+
+        let (gas_used, transaction_status, block_number) = if let Some(receipt) = receipt {
             (
                 receipt.gas_used,
                 if receipt.status == Some(U64::from(1)) { "Success" } else { "Failed" }.to_string(),
@@ -190,37 +362,84 @@ impl EVMChainManager {
             None
         };
 
-        // Analyze ERC20 transfers from transaction logs
-        let erc20_transfers = if let Some(receipt) = &receipt {
-            self.extract_erc20_transfers(receipt, &tx.from).await?
+        // Decode ERC-4337 user operations, whose smart accounts act instead of the bundler
+        let user_operations = receipt
+            .map(|receipt| self.extract_user_operations(receipt))
+            .unwrap_or_default();
+
+        // Decode EIP-7702 authorizations and the code each authority now delegates to
+        let authorizations = self.extract_authorizations(tx);
+        let max_authorization_gas = (!authorizations.is_empty()).then(|| {
+            authorizations.len() as u64 * authorizations::PER_EMPTY_ACCOUNT_COST
+        });
+        let delegations = authorizations::delegations(&authorizations);
+
+        // Separate the gas payer from the user of relayed transactions
+        let gas_attribution = self.extract_gas_attribution(tx, receipt, &user_operations, None);
+
+        // Analyze ERC20 transfers from transaction logs, sent by the sender or its smart accounts
+        let mut senders = vec![format!("{:?}", tx.from)];
+        // Any contract can emit UserOperationEvent, only EntryPoints vouch for the sender
+        senders.extend(user_operations.iter().filter(|operation| operation.is_from_entry_point()).map(|operation| operation.sender.clone()));
+        let erc20_transfers = if let Some(receipt) = receipt {
+            self.extract_erc20_transfers(receipt, &senders)?
         } else {
             Vec::new()
         };
+
+        // Net native and token changes per address from gas, value and token events
+        let balance_ledger = self.balance_ledger(tx, receipt, transaction_fee, None);
+
+        // Decode DEX swaps and resolve their tokens from the transfers of each hop
+        let swaps = receipt
+            .map(|receipt| self.extract_swaps(chain_key, tx, receipt))
+            .unwrap_or_default();
+
+        // Decode Wormhole messages published or redeemed by the transaction
+        let wormhole_messages = self.extract_wormhole_messages(chain_key, tx, receipt);
+
+        // Decode CCTP burns and mints with their domains and nonces
+        let cctp_transfers = receipt
+            .map(|receipt| self.extract_cctp_transfers(chain_key, receipt))
+            .unwrap_or_default();
+
+        // Decode allowances granted through approvals and signed permits
+        let approvals = self.extract_approvals(tx, receipt);
         
         Ok(TransactionAnalysis {
-            tx_hash: tx_hash.to_string(),
-            chain_name: chain_config.name.clone(),
+            tx_hash: format!("{:?}", tx.hash),
+            chain_name,
             gas_used,
             gas_price: tx.gas_price,
             gas_limit: tx.gas,
             transaction_fee,
             erc20_transfers,
+            swaps,
+            wormhole_messages,
+            cctp_transfers,
+            approvals,
+            user_operations,
+            authorizations,
+            max_authorization_gas,
+            delegations,
+            gas_attribution,
+            balance_ledger,
+            balance_ledger_source: EVMBalanceSource::Transaction,
+            state_diff: None,
             transaction_status,
             block_number,
+            block_timestamp: block.map(|block| block.timestamp),
+            base_fee_per_gas: block.and_then(|block| block.base_fee_per_gas),
             from_address: format!("{:?}", tx.from),
             to_address: tx.to.map(|addr| format!("{:?}", addr)),
             value: tx.value,
         })
     }
     
-    async fn extract_erc20_transfers(&self, receipt: &TransactionReceipt, tx_sender: &H160) -> Result<Vec<ERC20Transfer>> {
+    fn extract_erc20_transfers(&self, receipt: &TransactionReceipt, senders: &[String]) -> Result<Vec<ERC20Transfer>> {
         let mut transfers = Vec::new();
         
-        // ERC20 Transfer event signature: Transfer(address,address,uint256)
-        let transfer_event_signature = H256::from_str("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")?;
-        
-        // Format transaction sender address for comparison
-        let tx_sender_formatted = format!("{:?}", tx_sender);
+        let transfer_event_signature = H256::from_str(TRANSFER_EVENT_SIGNATURE)?;
         
         for log in &receipt.logs {
             // Check if this is a Transfer event
@@ -229,8 +448,8 @@ impl EVMChainManager {
                 let from_address = format!("{:?}", H160::from(log.topics[1]));
                 let to_address = format!("{:?}", H160::from(log.topics[2]));
                 
-                // Only include transfers where the transaction sender is the from_address
-                if from_address == tx_sender_formatted {
+                // Only include transfers where a sender is the from_address
+                if senders.contains(&from_address) {
                     // Parse amount from data field
                     let amount = if log.data.len() >= 32 {
                         U256::from_big_endian(&log.data[..32])
@@ -251,3 +470,25 @@ impl EVMChainManager {
         Ok(transfers)
     }
 }
+
+/// Parse an RPC object from JSON, either a full JSON-RPC response or its `result`
+pub fn parse_rpc_json<T: DeserializeOwned>(json: &str) -> Result<T> {
+    parse_rpc_value(serde_json::from_str(json)?)
+}
+
+/// Same as [`parse_rpc_json`] for already parsed JSON
+pub fn parse_rpc_value<T: DeserializeOwned>(mut value: serde_json::Value) -> Result<T> {
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// ABI encoded `bytes` at `offset`: a length word followed by the data
+fn abi_bytes(data: &[u8], offset: usize) -> Option<&[u8]> {
+    let length = U256::from_big_endian(data.get(offset..offset.checked_add(32)?)?);
+    if length > U256::from(data.len()) {
+        return None;
+    }
+    data.get(offset + 32..offset + 32 + length.low_u64() as usize)
+}
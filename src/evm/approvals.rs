@@ -0,0 +1,204 @@
+use super::EVMChainManager;
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Approval(address indexed owner, address indexed spender, uint256 value)
+const APPROVAL_EVENT_SIGNATURE: &str =
+    "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+/// Approval(address indexed owner, address indexed token, address indexed spender, uint160 amount, uint48 expiration)
+const PERMIT2_APPROVAL_EVENT_SIGNATURE: &str =
+    "0xda9fa7c1b00402c17d0161b249b1ab8bbec047c5a52207b9c112deffd817036b";
+/// Permit(address indexed owner, address indexed token, address indexed spender, uint160 amount, uint48 expiration, uint48 nonce)
+const PERMIT2_PERMIT_EVENT_SIGNATURE: &str =
+    "0xc6a377bfc4eb120024a8ac08eef205be16b817020812c73223e81d1bdb9708ec";
+
+/// permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s)
+const PERMIT_SELECTOR: [u8; 4] = [0xd5, 0x05, 0xac, 0xcf];
+/// permit(address holder, address spender, uint256 nonce, uint256 expiry, bool allowed, uint8 v, bytes32 r, bytes32 s), used by DAI
+const DAI_PERMIT_SELECTOR: [u8; 4] = [0x8f, 0xcb, 0xaf, 0x0c];
+/// selfPermit(address token, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) of Uniswap routers
+const SELF_PERMIT_SELECTOR: [u8; 4] = [0xf3, 0x99, 0x5c, 0x67];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EVMApprovalKind {
+    /// ERC20 `Approval` event
+    Erc20,
+    /// Permit2 allowance set through `approve`
+    Permit2Approval,
+    /// Permit2 allowance set from a signed permit
+    Permit2Permit,
+    /// EIP-2612 `permit` call, including DAI style and router `selfPermit` calls
+    Eip2612Permit,
+}
+
+/// An allowance granted by the transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMApproval {
+    pub kind: EVMApprovalKind,
+    /// Contract that emitted the event or received the permit call
+    pub contract: Option<String>,
+    /// `None` for permit calls, which are decoded from the calldata
+    pub log_index: Option<U256>,
+    /// `None` for permits embedded in a multicall without a matching `Approval` event
+    pub token: Option<String>,
+    pub owner: String,
+    pub spender: String,
+    pub amount: U256,
+    /// Unix timestamp the Permit2 allowance expires at
+    pub expiration: Option<u64>,
+    /// Unix timestamp the permit signature is valid until
+    pub deadline: Option<U256>,
+    /// The amount is the maximum of its type, which spenders treat as never decreasing
+    pub unlimited: bool,
+}
+
+impl EVMChainManager {
+    /// Allowances from ERC20 and Permit2 events, followed by the permit calls of the calldata
+    pub(super) fn extract_approvals(
+        &self,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+    ) -> Vec<EVMApproval> {
+        let approval = H256::from_str(APPROVAL_EVENT_SIGNATURE).unwrap();
+        let permit2_approval = H256::from_str(PERMIT2_APPROVAL_EVENT_SIGNATURE).unwrap();
+        let permit2_permit = H256::from_str(PERMIT2_PERMIT_EVENT_SIGNATURE).unwrap();
+        let uint160_max = (U256::one() << 160) - 1;
+        let logs = receipt
+            .map(|receipt| receipt.logs.as_slice())
+            .unwrap_or_default();
+
+        let mut approvals = Vec::new();
+        for log in logs {
+            let Some(topic) = log.topics.first() else {
+                continue;
+            };
+            let word = |offset: usize| U256::from_big_endian(&log.data[offset..offset + 32]);
+
+            // ERC721 approvals share the signature but index the token id
+            if *topic == approval && log.topics.len() == 3 && log.data.len() >= 32 {
+                let amount = word(0);
+                approvals.push(EVMApproval {
+                    kind: EVMApprovalKind::Erc20,
+                    contract: Some(format!("{:?}", log.address)),
+                    log_index: log.log_index,
+                    token: Some(format!("{:?}", log.address)),
+                    owner: format!("{:?}", H160::from(log.topics[1])),
+                    spender: format!("{:?}", H160::from(log.topics[2])),
+                    amount,
+                    expiration: None,
+                    deadline: None,
+                    unlimited: amount == U256::MAX,
+                });
+            } else if (*topic == permit2_approval || *topic == permit2_permit)
+                && log.topics.len() == 4
+                && log.data.len() >= 64
+            {
+                let amount = word(0);
+                approvals.push(EVMApproval {
+                    kind: if *topic == permit2_approval {
+                        EVMApprovalKind::Permit2Approval
+                    } else {
+                        EVMApprovalKind::Permit2Permit
+                    },
+                    contract: Some(format!("{:?}", log.address)),
+                    log_index: log.log_index,
+                    token: Some(format!("{:?}", H160::from(log.topics[2]))),
+                    owner: format!("{:?}", H160::from(log.topics[1])),
+                    spender: format!("{:?}", H160::from(log.topics[3])),
+                    amount,
+                    expiration: Some(word(32).low_u64()),
+                    deadline: None,
+                    unlimited: amount >= uint160_max,
+                });
+            }
+        }
+
+        // Permits are either the call itself or embedded as `bytes` in a multicall,
+        // where calls start on a word boundary of the arguments
+        let input = tx.input.as_ref();
+        let embedded = (4..input.len()).step_by(32).map(|offset| (offset, None));
+        for (offset, target) in std::iter::once((0, tx.to)).chain(embedded) {
+            let Some(mut permit) = input
+                .get(offset..)
+                .and_then(|call| decode_permit(call, tx.from, tx.to))
+            else {
+                continue;
+            };
+            if let Some(target) = target {
+                permit.contract = Some(format!("{:?}", target));
+                permit.token.get_or_insert_with(|| format!("{:?}", target));
+            }
+            // The token of an embedded permit is the contract of the resulting approval
+            if permit.token.is_none() {
+                permit.token = approvals
+                    .iter()
+                    .find(|approval| {
+                        approval.kind == EVMApprovalKind::Erc20
+                            && approval.owner == permit.owner
+                            && approval.spender == permit.spender
+                            && approval.amount == permit.amount
+                    })
+                    .and_then(|approval| approval.token.clone());
+            }
+            approvals.push(permit);
+        }
+
+        approvals
+    }
+}
+
+/// Decode a permit call. `selfPermit` grants the router, called by the sender, an allowance
+fn decode_permit(call: &[u8], from: H160, router: Option<H160>) -> Option<EVMApproval> {
+    let selector = call.get(..4)?;
+    let args = &call[4..];
+    let word = |index: usize| {
+        Some(U256::from_big_endian(
+            args.get(index * 32..index * 32 + 32)?,
+        ))
+    };
+    let address = |index: usize| {
+        let word = args.get(index * 32..index * 32 + 32)?;
+        Some(format!("{:?}", H160::from_slice(&word[12..])))
+    };
+
+    let (token, owner, spender, amount, deadline) = if selector == PERMIT_SELECTOR {
+        word(6)?;
+        (None, address(0)?, address(1)?, word(2)?, word(3)?)
+    } else if selector == DAI_PERMIT_SELECTOR {
+        word(7)?;
+        // DAI permits either grant an unlimited allowance or revoke it
+        let amount = if word(4)?.is_zero() {
+            U256::zero()
+        } else {
+            U256::MAX
+        };
+        (None, address(0)?, address(1)?, amount, word(3)?)
+    } else if selector == SELF_PERMIT_SELECTOR {
+        word(5)?;
+        let router = format!("{:?}", router?);
+        (
+            Some(address(0)?),
+            format!("{:?}", from),
+            router,
+            word(1)?,
+            word(2)?,
+        )
+    } else {
+        return None;
+    };
+
+    Some(EVMApproval {
+        kind: EVMApprovalKind::Eip2612Permit,
+        contract: None,
+        log_index: None,
+        token,
+        owner,
+        spender,
+        amount,
+        expiration: None,
+        deadline: Some(deadline),
+        unlimited: amount == U256::MAX,
+    })
+}
@@ -0,0 +1,117 @@
+use super::EVMChainManager;
+use ethers::prelude::*;
+use ethers::utils::{keccak256, rlp::RlpStream};
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the signed authorization message, `keccak256(MAGIC || rlp([chain_id, address, nonce]))`
+const AUTHORIZATION_MAGIC: u8 = 0x05;
+/// Intrinsic gas charged per authorization tuple, part of it refunded for existing accounts
+pub const PER_EMPTY_ACCOUNT_COST: u64 = 25000;
+/// secp256k1n / 2, the largest `s` of a valid signature since EIP-2
+const SECP256K1N_HALF: &str = "0x7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0";
+
+/// Authorization tuple as returned by the RPC in `authorizationList`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcAuthorization {
+    chain_id: U256,
+    address: H160,
+    nonce: U64,
+    /// Older nodes report `v` instead of `yParity`
+    y_parity: Option<U64>,
+    v: Option<U64>,
+    r: U256,
+    s: U256,
+}
+
+/// An EIP-7702 authorization tuple of a set-code transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMAuthorization {
+    /// 0 when the authorization is valid on every chain
+    pub chain_id: U256,
+    /// Contract whose code the authority delegates to, the zero address clears the delegation
+    pub delegate: String,
+    pub nonce: u64,
+    /// Recovered signer, `None` when the signature is invalid, has a high `s` or a `y_parity`
+    /// other than 0 or 1
+    pub authority: Option<String>,
+    /// The chain id is 0 or the transaction's. Nonce mismatches, which also skip the tuple,
+    /// need the authority's state and are not checked
+    pub chain_valid: bool,
+}
+
+/// Code an EOA was delegated to by the transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMDelegation {
+    pub authority: String,
+    /// `None` when the delegation was cleared
+    pub delegate: Option<String>,
+}
+
+impl EVMChainManager {
+    /// Authorization list of a type-4 transaction, in order, with the recovered authorities
+    pub(super) fn extract_authorizations(&self, tx: &Transaction) -> Vec<EVMAuthorization> {
+        let Some(Ok(list)) = tx
+            .other
+            .get_deserialized::<Vec<RpcAuthorization>>("authorizationList")
+        else {
+            return Vec::new();
+        };
+
+        list.into_iter()
+            .map(|authorization| {
+                let chain_valid =
+                    authorization.chain_id.is_zero() || Some(authorization.chain_id) == tx.chain_id;
+                EVMAuthorization {
+                    authority: recover_authority(&authorization).map(|a| format!("{:?}", a)),
+                    chain_id: authorization.chain_id,
+                    delegate: format!("{:?}", authorization.address),
+                    nonce: authorization.nonce.as_u64(),
+                    chain_valid,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Delegation left in place for each authority, the last applicable tuple winning
+pub(super) fn delegations(authorizations: &[EVMAuthorization]) -> Vec<EVMDelegation> {
+    let zero = format!("{:?}", H160::zero());
+    let mut delegations: Vec<EVMDelegation> = Vec::new();
+    for authorization in authorizations.iter().filter(|a| a.chain_valid) {
+        let Some(authority) = &authorization.authority else {
+            continue;
+        };
+        let delegate = (authorization.delegate != zero).then(|| authorization.delegate.clone());
+        match delegations.iter_mut().find(|d| &d.authority == authority) {
+            Some(delegation) => delegation.delegate = delegate,
+            None => delegations.push(EVMDelegation {
+                authority: authority.clone(),
+                delegate,
+            }),
+        }
+    }
+    delegations
+}
+
+fn recover_authority(authorization: &RpcAuthorization) -> Option<H160> {
+    // Clients skip tuples with malleable signatures instead of recovering them
+    let y_parity = authorization.y_parity.or(authorization.v)?.as_u64();
+    if y_parity > 1 || authorization.s > U256::from_str_radix(SECP256K1N_HALF, 16).ok()? {
+        return None;
+    }
+
+    let mut stream = RlpStream::new_list(3);
+    stream.append(&authorization.chain_id);
+    stream.append(&authorization.address);
+    stream.append(&authorization.nonce.as_u64());
+    let mut message = vec![AUTHORIZATION_MAGIC];
+    message.extend_from_slice(&stream.out());
+
+    let signature = Signature {
+        r: authorization.r,
+        s: authorization.s,
+        v: y_parity,
+    };
+    signature.recover(H256::from(keccak256(message))).ok()
+}
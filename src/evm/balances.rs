@@ -0,0 +1,178 @@
+use super::{EVMChainManager, EVMStateDiff, TRANSFER_EVENT_SIGNATURE};
+use crate::ledger::{BalanceChange, BalanceLedger};
+use anyhow::Result;
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// Deposit(address indexed dst, uint256 wad)
+const DEPOSIT_EVENT_SIGNATURE: &str =
+    "0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c";
+/// Withdrawal(address indexed src, uint256 wad)
+const WITHDRAWAL_EVENT_SIGNATURE: &str =
+    "0x7fcf532c15f0a6db0bd6d0e038bea71d30d808c7d98cb3bf7268a95bf5081b65";
+
+/// WETH9 style wrapped native tokens, which mint and burn without `Transfer` events
+const WRAPPED_NATIVE_TOKENS: &[&str] = &[
+    "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+    "0x4200000000000000000000000000000000000006",
+    "0x82af49447d8a07e3bd95bd0d56f35241523fbab1",
+    "0x0d500b1d8e8ef31e21c99d1db9a6444d3adf1270",
+    "0xb31f66aa3c1e785363f0875a1b74e27b85fd66c7",
+];
+
+/// Where the native changes of the balance ledger come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EVMBalanceSource {
+    /// Gas and the top-level value only, internal transfers are missing
+    Transaction,
+    /// Gas and every value transfer of the `callTracer` call tree
+    CallTracer,
+    /// Balances before and after from `prestateTracer` in diff mode
+    PrestateTracer,
+}
+
+/// Native value moved by a call frame
+pub(super) struct InternalTransfer {
+    pub(super) from: String,
+    pub(super) to: String,
+    pub(super) value: U256,
+}
+
+impl EVMChainManager {
+    /// Native and token changes per address. Without internal transfers only the top-level
+    /// value is known
+    pub(super) fn balance_ledger(
+        &self,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+        transaction_fee: Option<U256>,
+        internal_transfers: Option<&[InternalTransfer]>,
+    ) -> Vec<BalanceChange> {
+        let mut ledger = BalanceLedger::default();
+        let from = format!("{:?}", tx.from);
+
+        // Gas is charged whether or not the transaction succeeds
+        if let Some(fee) = transaction_fee {
+            ledger.native(&from, -I256::from_raw(fee));
+        }
+
+        let succeeded = receipt.is_some_and(|receipt| receipt.status == Some(U64::from(1)));
+        match internal_transfers {
+            Some(transfers) => {
+                for transfer in transfers {
+                    let value = I256::from_raw(transfer.value);
+                    ledger.transfer(None, &transfer.from, &transfer.to, value);
+                }
+            }
+            None => {
+                if let (true, Some(to)) = (succeeded && !tx.value.is_zero(), tx.to) {
+                    let value = I256::from_raw(tx.value);
+                    ledger.transfer(None, &from, &format!("{:?}", to), value);
+                }
+            }
+        }
+
+        add_token_changes(&mut ledger, receipt);
+        ledger.into_changes()
+    }
+
+    /// Native changes read from a prestate diff, which includes fees paid to the block producer
+    pub(super) fn state_diff_ledger(
+        &self,
+        receipt: Option<&TransactionReceipt>,
+        state_diff: &EVMStateDiff,
+    ) -> Vec<BalanceChange> {
+        let mut ledger = BalanceLedger::default();
+        for account in &state_diff.accounts {
+            let change =
+                I256::from_raw(account.balance_after) - I256::from_raw(account.balance_before);
+            ledger.native(&account.address, change);
+        }
+        add_token_changes(&mut ledger, receipt);
+        ledger.into_changes()
+    }
+
+    /// Value transfers of the call tree from `debug_traceTransaction` with `callTracer`
+    pub(super) async fn trace_internal_transfers(
+        &self,
+        provider: &Provider<Http>,
+        tx_hash: H256,
+    ) -> Result<Vec<InternalTransfer>> {
+        let trace: Value = provider
+            .request(
+                "debug_traceTransaction",
+                (tx_hash, json!({ "tracer": "callTracer" })),
+            )
+            .await?;
+        let mut transfers = Vec::new();
+        call_transfers(&trace, &mut transfers);
+        Ok(transfers)
+    }
+}
+
+/// Token changes from `Transfer` logs and wrapped native deposits and withdrawals
+fn add_token_changes(ledger: &mut BalanceLedger, receipt: Option<&TransactionReceipt>) {
+    let transfer = H256::from_str(TRANSFER_EVENT_SIGNATURE).unwrap();
+    let deposit = H256::from_str(DEPOSIT_EVENT_SIGNATURE).unwrap();
+    let withdrawal = H256::from_str(WITHDRAWAL_EVENT_SIGNATURE).unwrap();
+    for log in receipt.iter().flat_map(|receipt| &receipt.logs) {
+        if log.topics.is_empty() || log.data.len() < 32 {
+            continue;
+        }
+        let token = format!("{:?}", log.address);
+        let amount = I256::from_raw(U256::from_big_endian(&log.data[..32]));
+
+        // ERC721 transfers index the token id and carry no data
+        if log.topics[0] == transfer && log.topics.len() == 3 {
+            let from = format!("{:?}", H160::from(log.topics[1]));
+            let to = format!("{:?}", H160::from(log.topics[2]));
+            ledger.transfer(Some(&token), &from, &to, amount);
+        } else if (log.topics[0] == deposit || log.topics[0] == withdrawal)
+            && log.topics.len() == 2
+            && WRAPPED_NATIVE_TOKENS.contains(&token.as_str())
+        {
+            let account = format!("{:?}", H160::from(log.topics[1]));
+            let change = if log.topics[0] == deposit {
+                amount
+            } else {
+                -amount
+            };
+            ledger.token(&account, &token, change);
+        }
+    }
+}
+
+/// Collect value transfers of a frame and its subcalls, skipping reverted subtrees
+fn call_transfers(frame: &Value, transfers: &mut Vec<InternalTransfer>) {
+    if frame.get("error").is_some() {
+        return;
+    }
+
+    // Delegate and static calls report the caller's context value without moving it
+    let moves_value = matches!(
+        frame["type"].as_str(),
+        Some("CALL" | "CREATE" | "CREATE2" | "SELFDESTRUCT")
+    );
+    let value = frame["value"]
+        .as_str()
+        .and_then(|value| U256::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .unwrap_or_default();
+    if let (true, Some(from), Some(to)) = (
+        moves_value && !value.is_zero(),
+        frame["from"].as_str(),
+        frame["to"].as_str(),
+    ) {
+        transfers.push(InternalTransfer {
+            from: from.to_lowercase(),
+            to: to.to_lowercase(),
+            value,
+        });
+    }
+
+    for call in frame["calls"].as_array().into_iter().flatten() {
+        call_transfers(call, transfers);
+    }
+}
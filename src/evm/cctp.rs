@@ -0,0 +1,124 @@
+use super::{abi_bytes, EVMChainManager};
+use crate::cctp::{self, CctpTransfer, CctpTransferKind};
+use anyhow::Result;
+use ethers::prelude::*;
+use std::str::FromStr;
+
+/// MessageSent(bytes message), emitted by the MessageTransmitter for every burn
+const MESSAGE_SENT_EVENT_SIGNATURE: &str =
+    "0x8c5261668696ce22758910d05bab8f186d6eb247ceac2af2e82c7dc17669b036";
+/// MessageReceived(address indexed caller, uint32 sourceDomain, uint64 indexed nonce, bytes32 sender, bytes messageBody)
+const MESSAGE_RECEIVED_V1_EVENT_SIGNATURE: &str =
+    "0x58200b4c34ae05ee816d710053fff3fb75af4395915d3d2a771b24aa10e3cc5d";
+/// MessageReceived(address indexed caller, uint32 sourceDomain, bytes32 indexed nonce, bytes32 sender, uint32 indexed finalityThresholdExecuted, bytes messageBody)
+const MESSAGE_RECEIVED_V2_EVENT_SIGNATURE: &str =
+    "0xff48c13eda96b1cceacc6b9edeedc9e9db9d6226afbc30146b720c19d3addb1c";
+/// MintAndWithdraw(address indexed mintRecipient, uint256 amount, address indexed mintToken)
+const MINT_AND_WITHDRAW_V1_EVENT_SIGNATURE: &str =
+    "0x1b2a7ff080b8cb6ff436ce0372e399692bbfb6d4ae5766fd8d58a7b8cc6142e6";
+/// MintAndWithdraw(address indexed mintRecipient, uint256 amount, address indexed mintToken, uint256 feeCollected)
+const MINT_AND_WITHDRAW_V2_EVENT_SIGNATURE: &str =
+    "0x50c55e915134d457debfa58eb6f4342956f8b0616d51a89a3659360178e1ab63";
+
+impl EVMChainManager {
+    /// Burns from `MessageSent` and mints from `MessageReceived` with their `MintAndWithdraw`
+    pub(super) fn extract_cctp_transfers(
+        &self,
+        chain_name: &str,
+        receipt: &TransactionReceipt,
+    ) -> Vec<CctpTransfer> {
+        let message_sent = H256::from_str(MESSAGE_SENT_EVENT_SIGNATURE).unwrap();
+        let received_v1 = H256::from_str(MESSAGE_RECEIVED_V1_EVENT_SIGNATURE).unwrap();
+        let received_v2 = H256::from_str(MESSAGE_RECEIVED_V2_EVENT_SIGNATURE).unwrap();
+        let mint_v1 = H256::from_str(MINT_AND_WITHDRAW_V1_EVENT_SIGNATURE).unwrap();
+        let mint_v2 = H256::from_str(MINT_AND_WITHDRAW_V2_EVENT_SIGNATURE).unwrap();
+
+        let mut transfers = Vec::new();
+        // The token messenger mints before the transmitter reports the received message
+        let mut last_mint: Option<&Log> = None;
+        for log in &receipt.logs {
+            let Some(topic) = log.topics.first() else {
+                continue;
+            };
+            let word = |offset: usize| U256::from_big_endian(&log.data[offset..offset + 32]);
+
+            if *topic == mint_v1 || *topic == mint_v2 {
+                last_mint = Some(log);
+            } else if *topic == message_sent && log.data.len() >= 64 {
+                let Some(message) = abi_bytes(&log.data, word(0).low_u64() as usize) else {
+                    continue;
+                };
+                if let Some(transfer) = cctp::parse_message(CctpTransferKind::Burn, message) {
+                    transfers.push(transfer);
+                }
+            } else if (*topic == received_v1 || *topic == received_v2)
+                && log.topics.len() >= 3
+                && log.data.len() >= 96
+            {
+                let Some(mut transfer) = abi_bytes(&log.data, word(64).low_u64() as usize)
+                    .and_then(|body| cctp::parse_burn_message(CctpTransferKind::Mint, body))
+                else {
+                    continue;
+                };
+                transfer.source_domain = word(0).low_u32();
+                transfer.destination_domain = cctp::domain(chain_name).unwrap_or_default();
+                transfer.nonce = Some(if *topic == received_v1 {
+                    U256::from_big_endian(log.topics[2].as_bytes()).to_string()
+                } else {
+                    format!("{:?}", log.topics[2])
+                });
+
+                if let Some(mint) = last_mint.take() {
+                    if mint.topics.len() >= 3 && mint.data.len() >= 32 {
+                        transfer.mint_token = Some(format!("{:?}", H160::from(mint.topics[2])));
+                        transfer.minted_amount =
+                            Some(U256::from_big_endian(&mint.data[..32]).to_string());
+                    }
+                }
+                transfers.push(transfer);
+            }
+        }
+
+        transfers
+    }
+
+    /// Search recent blocks of the destination chain for the mint of a burn
+    pub(crate) async fn find_cctp_mint(
+        &self,
+        chain_name: &str,
+        burn: &CctpTransfer,
+    ) -> Result<Option<String>> {
+        let provider = self
+            .providers
+            .get(chain_name)
+            .ok_or_else(|| anyhow::anyhow!("Chain not supported: {}", chain_name))?;
+
+        let Some(nonce) = &burn.nonce else {
+            return Ok(None);
+        };
+        let (signature, nonce) = if burn.version == 1 {
+            let mut word = [0u8; 32];
+            U256::from_dec_str(nonce)?.to_big_endian(&mut word);
+            (MESSAGE_RECEIVED_V1_EVENT_SIGNATURE, H256::from(word))
+        } else {
+            (MESSAGE_RECEIVED_V2_EVENT_SIGNATURE, H256::from_str(nonce)?)
+        };
+
+        let latest = provider.get_block_number().await?;
+        let filter = Filter::new()
+            .topic0(H256::from_str(signature)?)
+            .topic2(nonce)
+            .from_block(latest.saturating_sub(U64::from(self.cctp_search_blocks)));
+        let logs = provider.get_logs(&filter).await?;
+
+        // Nonces are only unique per source domain
+        Ok(logs
+            .into_iter()
+            .find(|log| {
+                log.data.len() >= 32
+                    && U256::from_big_endian(&log.data[..32]) == U256::from(burn.source_domain)
+            })
+            .and_then(|log| log.transaction_hash)
+            .map(|hash| format!("{:?}", hash)))
+    }
+}
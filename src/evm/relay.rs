@@ -0,0 +1,242 @@
+use super::balances::InternalTransfer;
+use super::{ERC20Transfer, EVMChainManager, EVMUserOperation, TRANSFER_EVENT_SIGNATURE};
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Forwarder and relay hub calls carrying the signed request of the user. The path leads to
+/// the `from` address through the tuple offsets of the arguments
+const RELAY_CALLS: &[([u8; 4], &str, &[usize])] = &[
+    // execute((address from, address to, uint256 value, uint256 gas, uint256 nonce, bytes data), bytes signature)
+    ([0x47, 0x15, 0x3f, 0x82], "ERC-2771 forwarder", &[0, 0]),
+    // execute((address from, address to, uint256 value, uint256 gas, uint48 deadline, bytes data, bytes signature))
+    ([0xdf, 0x90, 0x5c, 0xaf], "ERC-2771 forwarder", &[0, 0]),
+    // execute((address from, address to, uint256 value, uint256 gas, uint256 nonce, bytes data, uint256 validUntilTime), bytes32, bytes32, bytes, bytes)
+    ([0xe0, 0x24, 0xdc, 0x7f], "GSN", &[0, 0]),
+    // GSN v3 relayCall(string domainSeparatorName, uint256 maxAcceptanceBudget, (ForwardRequest request, RelayData relayData), bytes signature, bytes approvalData)
+    // with RelayData (uint256 maxFeePerGas, uint256 maxPriorityFeePerGas, uint256 transactionCalldataGasUsed, address relayWorker, address paymaster, address forwarder, bytes paymasterData, uint256 clientId)
+    ([0x6c, 0xa8, 0x62, 0xe2], "GSN", &[2, 0, 0]),
+    // GSN v2 relayCall(uint256 maxAcceptanceBudget, (ForwardRequest request, RelayData relayData), bytes signature, bytes approvalData, uint256 externalGasLimit)
+    // with RelayData (uint256 gasPrice, uint256 pctRelayFee, uint256 baseRelayFee, address relayWorker, address paymaster, address forwarder, bytes paymasterData, uint256 clientId)
+    ([0x10, 0xc4, 0x54, 0x31], "GSN", &[1, 0, 0]),
+    // sponsoredCallERC2771((uint256 chainId, address target, bytes data, address user, uint256 userNonce, uint256 userDeadline), address sponsor, ...)
+    ([0x41, 0x5e, 0x51, 0x18], "Gelato", &[0, 3]),
+];
+
+/// Gelato relay contracts, deployed at the same address on every supported chain
+const GELATO_RELAYS: &[&str] = &[
+    "0xabcc9b596420a9e9172fd5938620e265a0f9df92",
+    "0xb539068872230f20456cf38ec52ef2f91af4ae49",
+    "0xd8253782c45a12053594b9deb72d8e8ab2fca54c",
+];
+
+/// Who paid the gas of the transaction and who it was sent for
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMGasAttribution {
+    /// Sender of the transaction, charged `transaction_fee`
+    pub gas_payer: String,
+    /// User the transaction was sent for, the gas payer when it was not relayed. Decoded from
+    /// the calldata, or the sender of the first reimbursement when the call does not carry it.
+    /// `None` when neither is known
+    pub beneficiary: Option<String>,
+    pub relayed: bool,
+    /// `ERC-2771 forwarder`, `GSN`, `Gelato`, `ERC-4337 bundler` or `Relayer` for the
+    /// configured relayer addresses
+    pub relay: Option<String>,
+    /// Forwarder, relay hub, EntryPoint or other contract the relayer called
+    pub relay_contract: Option<String>,
+    /// Token transfers to the gas payer or a configured relayer within the transaction
+    pub reimbursements: Vec<ERC20Transfer>,
+    /// Native value sent to the gas payer or a configured relayer by internal calls, only
+    /// known when internal transfers are traced
+    pub native_reimbursements: Vec<EVMNativeReimbursement>,
+    pub reimbursed: bool,
+}
+
+/// Native value paid back to a relayer within the transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMNativeReimbursement {
+    pub from_address: String,
+    pub to_address: String,
+    pub value: U256,
+}
+
+impl EVMChainManager {
+    /// Detect relayed transactions and the user they were relayed for. Native reimbursements
+    /// need the internal transfers of a trace
+    pub(super) fn extract_gas_attribution(
+        &self,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+        user_operations: &[EVMUserOperation],
+        internal_transfers: Option<&[InternalTransfer]>,
+    ) -> EVMGasAttribution {
+        let gas_payer = format!("{:?}", tx.from);
+        let to = tx.to.map(|to| format!("{:?}", to));
+
+        let relay_call = tx.input.get(..4).and_then(|selector| {
+            RELAY_CALLS
+                .iter()
+                .find(|(relay_selector, _, _)| selector == relay_selector)
+        });
+        let (relay, beneficiary) = if let Some((_, relay, path)) = relay_call {
+            let user = abi_address(&tx.input[4..], path).map(|user| format!("{:?}", user));
+            (Some(relay.to_string()), user)
+        } else if to.as_deref().is_some_and(|to| GELATO_RELAYS.contains(&to)) {
+            (Some("Gelato".to_string()), None)
+        } else if let Some(operation) = user_operations
+            .iter()
+            .find(|operation| operation.is_from_entry_point())
+        {
+            // Bundles of several accounts are attributed to the first one
+            (
+                Some("ERC-4337 bundler".to_string()),
+                Some(operation.sender.clone()),
+            )
+        } else if self.relayer_addresses.contains(&gas_payer) {
+            (Some("Relayer".to_string()), None)
+        } else {
+            (None, Some(gas_payer.clone()))
+        };
+        let relayed = relay.is_some();
+
+        // Relayers are paid back in tokens or native value by the user or the relayed contract
+        let (reimbursements, native_reimbursements) = if relayed {
+            (
+                self.relayer_reimbursements(receipt, &gas_payer),
+                self.native_reimbursements(internal_transfers.unwrap_or_default(), &gas_payer),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let beneficiary = beneficiary.or_else(|| {
+            reimbursements
+                .first()
+                .map(|transfer| transfer.from_address.clone())
+                .or_else(|| {
+                    native_reimbursements
+                        .first()
+                        .map(|transfer| transfer.from_address.clone())
+                })
+        });
+
+        EVMGasAttribution {
+            gas_payer,
+            beneficiary,
+            relayed,
+            relay,
+            relay_contract: to.filter(|_| relayed),
+            reimbursed: !reimbursements.is_empty() || !native_reimbursements.is_empty(),
+            reimbursements,
+            native_reimbursements,
+        }
+    }
+
+    fn native_reimbursements(
+        &self,
+        internal_transfers: &[InternalTransfer],
+        gas_payer: &str,
+    ) -> Vec<EVMNativeReimbursement> {
+        internal_transfers
+            .iter()
+            .filter(|transfer| {
+                transfer.to == gas_payer || self.relayer_addresses.contains(&transfer.to)
+            })
+            .map(|transfer| EVMNativeReimbursement {
+                from_address: transfer.from.clone(),
+                to_address: transfer.to.clone(),
+                value: transfer.value,
+            })
+            .collect()
+    }
+
+    fn relayer_reimbursements(
+        &self,
+        receipt: Option<&TransactionReceipt>,
+        gas_payer: &str,
+    ) -> Vec<ERC20Transfer> {
+        let transfer_event = H256::from_str(TRANSFER_EVENT_SIGNATURE).unwrap();
+        receipt
+            .iter()
+            .flat_map(|receipt| &receipt.logs)
+            .filter(|log| {
+                log.topics.len() >= 3 && log.topics[0] == transfer_event && log.data.len() >= 32
+            })
+            .map(|log| ERC20Transfer {
+                token_address: format!("{:?}", log.address),
+                from_address: format!("{:?}", H160::from(log.topics[1])),
+                to_address: format!("{:?}", H160::from(log.topics[2])),
+                amount: U256::from_big_endian(&log.data[..32]),
+            })
+            .filter(|transfer| {
+                transfer.to_address == gas_payer
+                    || self.relayer_addresses.contains(&transfer.to_address)
+            })
+            .collect()
+    }
+}
+
+/// Follow tuple offsets to an address argument, each step indexing the words of a tuple
+fn abi_address(args: &[u8], path: &[usize]) -> Option<H160> {
+    let mut base = 0usize;
+    let (last, tuples) = path.split_last()?;
+    for index in tuples {
+        let offset = base.checked_add(index * 32)?;
+        let pointer = U256::from_big_endian(args.get(offset..offset + 32)?);
+        if pointer > U256::from(args.len()) {
+            return None;
+        }
+        base = base.checked_add(pointer.as_usize())?;
+    }
+    let offset = base.checked_add(last * 32)?;
+    Some(H160::from_slice(args.get(offset + 12..offset + 32)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// GSN v3 `relayCall` of a USDC `transfer` signed by `USER`, with placeholder relay worker,
+    /// paymaster and forwarder addresses
+    const GSN_V3_RELAY_CALL: &str = "6ca862e200000000000000000000000000000000000000000000000000000000000000a00000000000000000000000000000000000000000000000000000000000045a4400000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000036000000000000000000000000000000000000000000000000000000000000003e0000000000000000000000000000000000000000000000000000000000000001747534e2052656c61796564205472616e73616374696f6e000000000000000000000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000001600000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000186a0000000000000000000000000000000000000000000000000000000000000000700000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000006553ff100000000000000000000000000000000000000000000000000000000000000004a9059cbb0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000006fc23ac00000000000000000000000000000000000000000000000000000000003b9aca0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001111111111111111111111111111111111111111000000000000000000000000222222222222222222222222222222222222222200000000000000000000000033333333333333333333333333333333333333330000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000041ababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababab000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
+    /// ERC-2771 forwarder `execute` of the same transfer
+    const FORWARDER_EXECUTE: &str = "47153f82000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000001400000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000186a0000000000000000000000000000000000000000000000000000000000000000300000000000000000000000000000000000000000000000000000000000000c00000000000000000000000000000000000000000000000000000000000000004a9059cbb000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000041ababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababab00000000000000000000000000000000000000000000000000000000000000";
+
+    const USER: &str = "0x8ba1f109551bd432803012645ac136ddd64dba72";
+
+    /// Relay name and user decoded from calldata as `extract_gas_attribution` does
+    fn relayed_user(calldata: &str) -> Option<(&'static str, H160)> {
+        let input = hex::decode(calldata).unwrap();
+        let (_, relay, path) = RELAY_CALLS
+            .iter()
+            .find(|(selector, _, _)| input[..4] == selector[..])?;
+        Some((*relay, abi_address(&input[4..], path)?))
+    }
+
+    #[test]
+    fn decodes_gsn_v3_relay_call_sender() {
+        assert_eq!(
+            relayed_user(GSN_V3_RELAY_CALL),
+            Some(("GSN", H160::from_str(USER).unwrap()))
+        );
+    }
+
+    #[test]
+    fn decodes_forwarder_execute_sender() {
+        assert_eq!(
+            relayed_user(FORWARDER_EXECUTE),
+            Some(("ERC-2771 forwarder", H160::from_str(USER).unwrap()))
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_offsets() {
+        let mut args = hex::decode(&GSN_V3_RELAY_CALL[8..]).unwrap();
+        // Point the relay request past the end of the calldata
+        args[2 * 32 + 31] = 0xff;
+        args[2 * 32 + 30] = 0xff;
+        assert_eq!(abi_address(&args, &[2, 0, 0]), None);
+        assert_eq!(abi_address(&args[..64], &[2, 0, 0]), None);
+    }
+}
@@ -0,0 +1,362 @@
+use super::EVMChainManager;
+use anyhow::Result;
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Tokens whose `balanceOf` mapping slot is known, deployed behind fixed addresses
+const TOKEN_BALANCE_SLOTS: &[(&str, u64)] = &[
+    // FiatToken (USDC)
+    ("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", 9),
+    ("0x833589fcd6edb6e08f4c7c32d4f71b54bda02913", 9),
+    ("0xaf88d065e77c8cc2239327c5edb3a432268e5831", 9),
+    ("0x0b2c639c533813f4aa9d7837caf62653d097ff85", 9),
+    ("0x3c499c542cef5e3811e1192ce70d8cc03d5c3359", 9),
+    ("0xb97ef9ef8734c71904d8002f8b6bc66dd9c48a6e", 9),
+    // USDT and DAI
+    ("0xdac17f958d2ee523a2206206994597c13d831ec7", 2),
+    ("0x6b175474e89094c44da98b954eedeac495271d0f", 2),
+    // WETH9
+    ("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", 3),
+    ("0x4200000000000000000000000000000000000006", 3),
+];
+
+/// State changes of a transaction from `prestateTracer` in diff mode
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMStateDiff {
+    pub accounts: Vec<EVMAccountDiff>,
+    /// Balance slot changes of tokens with a known layout, including those without events
+    pub token_balance_changes: Vec<EVMTokenSlotChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMAccountDiff {
+    pub address: String,
+    pub balance_before: U256,
+    pub balance_after: U256,
+    /// Signed decimal change of the native balance
+    pub balance_change: String,
+    pub nonce_before: u64,
+    pub nonce_after: u64,
+    pub code_changed: bool,
+    /// Destroyed by the transaction
+    pub deleted: bool,
+    pub storage_changes: Vec<EVMStorageChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMStorageChange {
+    pub slot: H256,
+    pub before: H256,
+    pub after: H256,
+}
+
+/// A changed storage slot decoded as a token balance
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMTokenSlotChange {
+    pub token: String,
+    pub holder: String,
+    pub slot: H256,
+    pub balance_before: U256,
+    pub balance_after: U256,
+    /// Signed decimal change in the token's base units
+    pub change: String,
+}
+
+impl EVMChainManager {
+    /// Trace the state diff and decode token balance slots of `holders`
+    pub(super) async fn fetch_state_diff(
+        &self,
+        provider: &Provider<Http>,
+        tx_hash: H256,
+        holders: &[H160],
+    ) -> Result<EVMStateDiff> {
+        let trace: Value = provider
+            .request(
+                "debug_traceTransaction",
+                (
+                    tx_hash,
+                    json!({ "tracer": "prestateTracer", "tracerConfig": { "diffMode": true } }),
+                ),
+            )
+            .await?;
+        Ok(parse_state_diff(&trace, holders))
+    }
+}
+
+/// In diff mode `pre` holds the modified accounts, `post` only the fields that changed.
+/// Slots cleared to zero and deleted accounts are missing from `post`
+fn parse_state_diff(trace: &Value, holders: &[H160]) -> EVMStateDiff {
+    let empty = serde_json::Map::new();
+    let pre = trace["pre"].as_object().unwrap_or(&empty);
+    let post = trace["post"].as_object().unwrap_or(&empty);
+
+    let mut addresses: Vec<&String> = pre.keys().collect();
+    addresses.extend(post.keys().filter(|address| !pre.contains_key(*address)));
+
+    // Balances of the touched accounts themselves are decoded too
+    let mut holders = holders.to_vec();
+    for address in addresses
+        .iter()
+        .filter_map(|address| address.parse::<H160>().ok())
+    {
+        if !holders.contains(&address) {
+            holders.push(address);
+        }
+    }
+
+    let mut accounts = Vec::new();
+    let mut token_balance_changes = Vec::new();
+    for address in addresses {
+        let before = pre.get(address).unwrap_or(&Value::Null);
+        let after = post.get(address).unwrap_or(&Value::Null);
+        let deleted = pre.contains_key(address) && !post.contains_key(address);
+
+        let balance_before = hex_u256(&before["balance"]);
+        let balance_after = if deleted {
+            U256::zero()
+        } else if after["balance"].is_null() {
+            balance_before
+        } else {
+            hex_u256(&after["balance"])
+        };
+        let nonce_before = before["nonce"].as_u64().unwrap_or_default();
+        let nonce_after = after["nonce"].as_u64().unwrap_or(nonce_before);
+
+        let mut slots: Vec<&String> = Vec::new();
+        for state in [before, after] {
+            for slot in state["storage"]
+                .as_object()
+                .into_iter()
+                .flat_map(|s| s.keys())
+            {
+                if !slots.contains(&slot) {
+                    slots.push(slot);
+                }
+            }
+        }
+        let storage_changes: Vec<EVMStorageChange> = slots
+            .into_iter()
+            .filter_map(|slot| {
+                Some(EVMStorageChange {
+                    slot: slot.parse().ok()?,
+                    before: hex_h256(&before["storage"][slot]),
+                    after: hex_h256(&after["storage"][slot]),
+                })
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+
+        let address = address.to_lowercase();
+        if let Some((_, balance_slot)) = TOKEN_BALANCE_SLOTS
+            .iter()
+            .find(|(token, _)| *token == address)
+        {
+            token_balance_changes.extend(token_slot_changes(
+                &address,
+                *balance_slot,
+                &storage_changes,
+                &holders,
+            ));
+        }
+
+        accounts.push(EVMAccountDiff {
+            balance_change: (I256::from_raw(balance_after) - I256::from_raw(balance_before))
+                .to_string(),
+            address,
+            balance_before,
+            balance_after,
+            nonce_before,
+            nonce_after,
+            code_changed: !after["code"].is_null() || (deleted && !before["code"].is_null()),
+            deleted,
+            storage_changes,
+        });
+    }
+
+    EVMStateDiff {
+        accounts,
+        token_balance_changes,
+    }
+}
+
+/// Match changed slots against `keccak256(holder . balance_slot)` of each holder
+fn token_slot_changes(
+    token: &str,
+    balance_slot: u64,
+    storage_changes: &[EVMStorageChange],
+    holders: &[H160],
+) -> Vec<EVMTokenSlotChange> {
+    holders
+        .iter()
+        .filter_map(|holder| {
+            let mut key = [0u8; 64];
+            key[12..32].copy_from_slice(holder.as_bytes());
+            key[56..64].copy_from_slice(&balance_slot.to_be_bytes());
+            let slot = H256::from(keccak256(key));
+            let change = storage_changes.iter().find(|change| change.slot == slot)?;
+
+            let balance_before = U256::from_big_endian(change.before.as_bytes());
+            let balance_after = U256::from_big_endian(change.after.as_bytes());
+            Some(EVMTokenSlotChange {
+                token: token.to_string(),
+                holder: format!("{:?}", holder),
+                slot,
+                balance_before,
+                balance_after,
+                change: (I256::from_raw(balance_after) - I256::from_raw(balance_before))
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+fn hex_u256(value: &Value) -> U256 {
+    value
+        .as_str()
+        .and_then(|value| U256::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+        .unwrap_or_default()
+}
+
+/// Storage words are zero when missing
+fn hex_h256(value: &Value) -> H256 {
+    value
+        .as_str()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: &str = "0x8ba1f109551bd432803012645ac136ddd64dba72";
+    const RECIPIENT: &str = "0x2222222222222222222222222222222222222222";
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const DESTROYED: &str = "0xdeaddeaddeaddeaddeaddeaddeaddeaddeaddead";
+    /// `balanceOf` slots of the sender and the recipient in USDC
+    const SENDER_SLOT: &str = "0x4b899661c3e7ac47f4138226e5544a4b8b29d81dd4e3e11c6cfcb0a202ebdb73";
+    const RECIPIENT_SLOT: &str =
+        "0x3d7fc000e3f5fa268818a8a2c201da9ba83d04d1d8c60bde02e1e461f3fe95bf";
+
+    /// `prestateTracer` diff of the sender moving its whole 25 USDC balance to the recipient
+    /// and calling a contract that self-destructs, refunding its 256 wei
+    fn trace() -> Value {
+        json!({
+            "pre": {
+                SENDER: {
+                    "balance": "0xde0b6b3a7640000",
+                    "nonce": 7
+                },
+                USDC: {
+                    "balance": "0x0",
+                    "code": "0x6080",
+                    "nonce": 1,
+                    "storage": {
+                        SENDER_SLOT: "0x00000000000000000000000000000000000000000000000000000000017d7840"
+                    }
+                },
+                DESTROYED: {
+                    "balance": "0x100",
+                    "code": "0x6080",
+                    "nonce": 1
+                }
+            },
+            "post": {
+                SENDER: {
+                    "balance": "0xddf5d1802604000",
+                    "nonce": 8
+                },
+                USDC: {
+                    "storage": {
+                        RECIPIENT_SLOT: "0x00000000000000000000000000000000000000000000000000000000017d7840"
+                    }
+                }
+            }
+        })
+    }
+
+    fn account<'a>(diff: &'a EVMStateDiff, address: &str) -> &'a EVMAccountDiff {
+        diff.accounts
+            .iter()
+            .find(|account| account.address == address)
+            .unwrap()
+    }
+
+    #[test]
+    fn reports_native_balance_and_nonce_changes() {
+        let diff = parse_state_diff(&trace(), &[]);
+
+        let sender = account(&diff, SENDER);
+        assert_eq!(sender.balance_before, U256::exp10(18));
+        assert_eq!(sender.balance_change, "-380000000000000");
+        assert_eq!((sender.nonce_before, sender.nonce_after), (7, 8));
+        assert!(!sender.deleted);
+        assert!(!sender.code_changed);
+
+        // Unchanged fields are omitted from `post`
+        let usdc = account(&diff, USDC);
+        assert_eq!(usdc.balance_change, "0");
+        assert_eq!((usdc.nonce_before, usdc.nonce_after), (1, 1));
+        assert!(!usdc.code_changed);
+    }
+
+    #[test]
+    fn treats_missing_post_slots_as_zeroed() {
+        let diff = parse_state_diff(&trace(), &[]);
+
+        let usdc = account(&diff, USDC);
+        assert_eq!(usdc.storage_changes.len(), 2);
+        let cleared = usdc
+            .storage_changes
+            .iter()
+            .find(|change| change.slot == SENDER_SLOT.parse().unwrap())
+            .unwrap();
+        assert_eq!(cleared.before, H256::from_low_u64_be(25_000_000));
+        assert_eq!(cleared.after, H256::zero());
+        let set = usdc
+            .storage_changes
+            .iter()
+            .find(|change| change.slot == RECIPIENT_SLOT.parse().unwrap())
+            .unwrap();
+        assert_eq!(set.before, H256::zero());
+        assert_eq!(set.after, H256::from_low_u64_be(25_000_000));
+    }
+
+    #[test]
+    fn marks_accounts_missing_from_post_as_deleted() {
+        let diff = parse_state_diff(&trace(), &[]);
+
+        let destroyed = account(&diff, DESTROYED);
+        assert!(destroyed.deleted);
+        assert!(destroyed.code_changed);
+        assert_eq!(destroyed.balance_after, U256::zero());
+        assert_eq!(destroyed.balance_change, "-256");
+    }
+
+    #[test]
+    fn decodes_token_balance_slots_of_holders() {
+        // The sender is a touched account, the recipient only a requested holder
+        let diff = parse_state_diff(&trace(), &[RECIPIENT.parse().unwrap()]);
+
+        let changes: Vec<(&str, &str, &str)> = diff
+            .token_balance_changes
+            .iter()
+            .map(|change| {
+                (
+                    change.token.as_str(),
+                    change.holder.as_str(),
+                    change.change.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&(USDC, SENDER, "-25000000")));
+        assert!(changes.contains(&(USDC, RECIPIENT, "25000000")));
+
+        // Without the recipient as holder its slot stays undecoded
+        let diff = parse_state_diff(&trace(), &[]);
+        assert_eq!(diff.token_balance_changes.len(), 1);
+    }
+}
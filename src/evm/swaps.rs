@@ -0,0 +1,381 @@
+use super::{EVMChainManager, TRANSFER_EVENT_SIGNATURE};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Swap(address indexed sender, uint256 amount0In, uint256 amount1In, uint256 amount0Out, uint256 amount1Out, address indexed to)
+const UNISWAP_V2_SWAP_EVENT_SIGNATURE: &str =
+    "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+/// Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick)
+const UNISWAP_V3_SWAP_EVENT_SIGNATURE: &str =
+    "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+/// Swap(bytes32 indexed id, address indexed sender, int128 amount0, int128 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick, uint24 fee)
+const UNISWAP_V4_SWAP_EVENT_SIGNATURE: &str =
+    "0x40e9cecb9f5f1f1c5b9c97dec2917b7ee92e57ba5563708daca94dd84ad7112f";
+
+/// decimals() of an ERC20 token
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// Aggregator and router contracts deployed at one address on every chain they support
+const AGGREGATOR_ROUTERS: &[(&str, &str)] = &[
+    ("0x1111111254eeb25477b68fb85ed929f73a960582", "1inch"),
+    ("0x111111125421ca6dc452d289314280a0f8842a65", "1inch"),
+    ("0xdef171fe48cf0115b1d80b88dc8eab59176fee57", "ParaSwap"),
+    ("0x6a000f20005980200259b80c5102003040001068", "ParaSwap"),
+    ("0x6131b5fae19ea4f9d964eac0408e4408b66337b5", "KyberSwap"),
+    ("0x9008d19f58aabd9ed0d60971565aa8510560ab41", "CoW Protocol"),
+    ("0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae", "LI.FI"),
+];
+
+/// Routers whose addresses differ between chains: the 0x exchange proxy and the Uniswap
+/// Universal Router, V1.2 and the V4 router
+const CHAIN_ROUTERS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "ethereum",
+        &[
+            ("0xdef1c0ded9bec7f1a1670819833240f027b25eff", "0x"),
+            (
+                "0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0x66a9893cc07d91d95644aedd05d03f95e1dba8af",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "base",
+        &[
+            (
+                "0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0x6ff5693b99212da76ad316178a184ab56d299b43",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "arbitrum",
+        &[
+            (
+                "0x5e325eda8064b456f4781070c0738d849c824258",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0xa51afafe0263b40edaef0df8781ea9aa03e381a3",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "optimism",
+        &[
+            (
+                "0xcb1355ff08ab38bbce60111f1bb2b784be25d7e8",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0x851116d9223fabed8e56c0e6b8ad0c31d98b3507",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "polygon",
+        &[
+            (
+                "0xec7be89e9d109e7e3fec59c222cf297125fefda2",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0x1095692a6237d83c6a72f3f5efedb9a670c49223",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "avalanche",
+        &[
+            (
+                "0x4dae2f939acf50408e13d58534ff8c2776d45265",
+                "Uniswap Universal Router",
+            ),
+            (
+                "0x94b75331ae8d42c1b61065089b7d48fe14aa73b7",
+                "Uniswap Universal Router",
+            ),
+        ],
+    ),
+    (
+        "unichain",
+        &[(
+            "0xef740bf23acae26f6492b10de645d6b98dc8eaf3",
+            "Uniswap Universal Router",
+        )],
+    ),
+];
+
+/// A single swap hop through a Uniswap V2, V3 or V4 style pool
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMSwap {
+    /// `Uniswap V2`, `Uniswap V3` or `Uniswap V4`, including forks emitting the same event
+    pub protocol: String,
+    /// Pool contract, or the pool id for V4 pools
+    pub pool: String,
+    /// PoolManager that emitted the event, V4 only
+    pub pool_manager: Option<String>,
+    /// Aggregator or router that routed the hop, when recognised
+    pub aggregator: Option<String>,
+    pub log_index: Option<U256>,
+    pub sender: String,
+    /// Receiver of the output, not part of V4 events
+    pub recipient: Option<String>,
+    /// Resolved from the ERC20 transfers of the hop, `None` for native ETH or unmatched transfers
+    pub token_in: Option<String>,
+    pub amount_in: U256,
+    pub token_out: Option<String>,
+    pub amount_out: U256,
+    /// Resolved with `decimals()` calls, `None` for offline analysis
+    pub token_in_decimals: Option<u8>,
+    pub token_out_decimals: Option<u8>,
+    /// Output per unit of input in whole tokens, when both decimals are known
+    pub price: Option<f64>,
+}
+
+/// ERC20 transfer from the receipt logs, with its position among them
+struct LogTransfer {
+    position: usize,
+    token: H160,
+    from: H160,
+    to: H160,
+    amount: U256,
+}
+
+impl EVMChainManager {
+    pub(super) fn extract_swaps(
+        &self,
+        chain_name: &str,
+        tx: &Transaction,
+        receipt: &TransactionReceipt,
+    ) -> Vec<EVMSwap> {
+        let v2_swap = H256::from_str(UNISWAP_V2_SWAP_EVENT_SIGNATURE).unwrap();
+        let v3_swap = H256::from_str(UNISWAP_V3_SWAP_EVENT_SIGNATURE).unwrap();
+        let v4_swap = H256::from_str(UNISWAP_V4_SWAP_EVENT_SIGNATURE).unwrap();
+        let transfers = log_transfers(receipt);
+        let tx_router = tx.to.and_then(|to| aggregator_name(chain_name, to));
+
+        let mut swaps = Vec::new();
+        for (position, log) in receipt.logs.iter().enumerate() {
+            let Some(topic) = log.topics.first() else {
+                continue;
+            };
+            if log.data.len() < 64 {
+                continue;
+            }
+            let word = |index: usize| &log.data[index * 32..(index + 1) * 32];
+
+            // (protocol, pool, sender, recipient, amount in, amount out)
+            let (protocol, pool, sender, recipient, amount_in, amount_out) =
+                if *topic == v2_swap && log.topics.len() >= 3 && log.data.len() >= 128 {
+                    let amount0_in = U256::from_big_endian(word(0));
+                    let amount1_in = U256::from_big_endian(word(1));
+                    let amount0_out = U256::from_big_endian(word(2));
+                    let amount1_out = U256::from_big_endian(word(3));
+                    let (amount_in, amount_out) = if amount0_in > U256::zero() {
+                        (amount0_in, amount1_out)
+                    } else {
+                        (amount1_in, amount0_out)
+                    };
+                    (
+                        "Uniswap V2",
+                        format!("{:?}", log.address),
+                        H160::from(log.topics[1]),
+                        Some(H160::from(log.topics[2])),
+                        amount_in,
+                        amount_out,
+                    )
+                } else if *topic == v3_swap && log.topics.len() >= 3 {
+                    // Positive amounts are paid into the pool
+                    let (amount_in, amount_out) = split_deltas(word(0), word(1), true);
+                    (
+                        "Uniswap V3",
+                        format!("{:?}", log.address),
+                        H160::from(log.topics[1]),
+                        Some(H160::from(log.topics[2])),
+                        amount_in,
+                        amount_out,
+                    )
+                } else if *topic == v4_swap && log.topics.len() >= 3 {
+                    // Amounts are deltas of the swapper, negative amounts are paid into the pool
+                    let (amount_in, amount_out) = split_deltas(word(0), word(1), false);
+                    (
+                        "Uniswap V4",
+                        format!("{:?}", log.topics[1]),
+                        H160::from(log.topics[2]),
+                        None,
+                        amount_in,
+                        amount_out,
+                    )
+                } else {
+                    continue;
+                };
+
+            // Tokens move between the swapper and the pool, or the PoolManager for V4
+            let token_in = nearest_transfer(&transfers, position, |t| {
+                t.to == log.address && t.amount == amount_in
+            });
+            let token_out = nearest_transfer(&transfers, position, |t| {
+                t.from == log.address && t.amount == amount_out
+            });
+
+            swaps.push(EVMSwap {
+                protocol: protocol.to_string(),
+                pool,
+                pool_manager: (*topic == v4_swap).then(|| format!("{:?}", log.address)),
+                aggregator: tx_router
+                    .or_else(|| aggregator_name(chain_name, sender))
+                    .map(str::to_string),
+                log_index: log.log_index,
+                sender: format!("{:?}", sender),
+                recipient: recipient.map(|recipient| format!("{:?}", recipient)),
+                token_in: token_in.map(|token| format!("{:?}", token)),
+                amount_in,
+                token_out: token_out.map(|token| format!("{:?}", token)),
+                amount_out,
+                token_in_decimals: None,
+                token_out_decimals: None,
+                price: None,
+            });
+        }
+
+        swaps
+    }
+
+    /// Resolve the decimals of the swapped tokens and price each hop in whole tokens. Tokens
+    /// whose `decimals()` call fails are left unpriced
+    pub(super) async fn fetch_swap_prices(
+        &self,
+        provider: &Provider<Http>,
+        block_number: Option<U64>,
+        swaps: &mut [EVMSwap],
+    ) {
+        let mut tokens: Vec<String> = Vec::new();
+        for swap in swaps.iter() {
+            for token in [&swap.token_in, &swap.token_out].into_iter().flatten() {
+                if !tokens.contains(token) {
+                    tokens.push(token.clone());
+                }
+            }
+        }
+
+        let mut decimals: HashMap<String, Option<u8>> = HashMap::new();
+        for token in tokens {
+            let Ok(address) = H160::from_str(&token) else {
+                continue;
+            };
+            let call: TypedTransaction = TransactionRequest::new()
+                .to(address)
+                .data(DECIMALS_SELECTOR.to_vec())
+                .into();
+            let token_decimals = provider
+                .call(&call, block_number.map(|number| number.into()))
+                .await
+                .ok()
+                .filter(|result| result.len() >= 32)
+                .map(|result| U256::from_big_endian(&result[..32]))
+                .filter(|value| *value <= U256::from(u8::MAX))
+                .map(|value| value.as_u32() as u8);
+            decimals.insert(token, token_decimals);
+        }
+
+        let lookup = |token: &Option<String>| *decimals.get(token.as_ref()?)?;
+        for swap in swaps {
+            swap.token_in_decimals = lookup(&swap.token_in);
+            swap.token_out_decimals = lookup(&swap.token_out);
+            swap.price = price(
+                swap.amount_in,
+                swap.amount_out,
+                swap.token_in_decimals,
+                swap.token_out_decimals,
+            );
+        }
+    }
+}
+
+fn aggregator_name(chain: &str, address: H160) -> Option<&'static str> {
+    let address = format!("{:?}", address);
+    CHAIN_ROUTERS
+        .iter()
+        .filter(|(router_chain, _)| *router_chain == chain)
+        .flat_map(|(_, routers)| routers.iter())
+        .chain(AGGREGATOR_ROUTERS)
+        .find(|(router, _)| *router == address)
+        .map(|(_, name)| *name)
+}
+
+fn log_transfers(receipt: &TransactionReceipt) -> Vec<LogTransfer> {
+    let transfer_event = H256::from_str(TRANSFER_EVENT_SIGNATURE).unwrap();
+    receipt
+        .logs
+        .iter()
+        .enumerate()
+        .filter(|(_, log)| {
+            log.topics.len() >= 3 && log.topics[0] == transfer_event && log.data.len() >= 32
+        })
+        .map(|(position, log)| LogTransfer {
+            position,
+            token: log.address,
+            from: H160::from(log.topics[1]),
+            to: H160::from(log.topics[2]),
+            amount: U256::from_big_endian(&log.data[..32]),
+        })
+        .collect()
+}
+
+/// Token of the matching transfer closest to the swap log
+fn nearest_transfer(
+    transfers: &[LogTransfer],
+    position: usize,
+    matches: impl Fn(&LogTransfer) -> bool,
+) -> Option<H160> {
+    transfers
+        .iter()
+        .filter(|transfer| matches(transfer))
+        .min_by_key(|transfer| transfer.position.abs_diff(position))
+        .map(|transfer| transfer.token)
+}
+
+/// Input and output amounts from signed token0 and token1 deltas
+fn split_deltas(amount0: &[u8], amount1: &[u8], positive_in: bool) -> (U256, U256) {
+    let amount0 = I256::from_raw(U256::from_big_endian(amount0));
+    let amount1 = I256::from_raw(U256::from_big_endian(amount1));
+    let token0_in = (amount0 > I256::zero()) == positive_in && !amount0.is_zero();
+    let (amount_in, amount_out) = if token0_in {
+        (amount0, amount1)
+    } else {
+        (amount1, amount0)
+    };
+    (amount_in.unsigned_abs(), amount_out.unsigned_abs())
+}
+
+/// Output per unit of input in whole tokens, the same unit as Solana swap prices
+fn price(
+    amount_in: U256,
+    amount_out: U256,
+    decimals_in: Option<u8>,
+    decimals_out: Option<u8>,
+) -> Option<f64> {
+    if amount_in.is_zero() {
+        return None;
+    }
+    let amount_in: f64 = amount_in.to_string().parse().ok()?;
+    let amount_out: f64 = amount_out.to_string().parse().ok()?;
+    let amount_in = amount_in / 10f64.powi(decimals_in? as i32);
+    let amount_out = amount_out / 10f64.powi(decimals_out? as i32);
+    Some(amount_out / amount_in)
+}
@@ -0,0 +1,109 @@
+use super::{abi_bytes, EVMChainManager};
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// UserOperationEvent(bytes32 indexed userOpHash, address indexed sender, address indexed paymaster, uint256 nonce, bool success, uint256 actualGasCost, uint256 actualGasUsed)
+const USER_OPERATION_EVENT_SIGNATURE: &str =
+    "0x49628fd1471006c1482da88028e9ce4dbb080b815c9b0344d39e5a8e6ec1419f";
+/// UserOperationRevertReason(bytes32 indexed userOpHash, address indexed sender, uint256 nonce, bytes revertReason)
+const USER_OPERATION_REVERT_REASON_EVENT_SIGNATURE: &str =
+    "0x1c4fada7374c0a9ee8841fc38afe82932dc0f8e69012e927f061a8bae611a201";
+
+/// EntryPoint deployments, at the same address on every supported chain
+const ENTRY_POINTS: &[(&str, &str)] = &[
+    ("0x5ff137d4b0fdcd49dca30c7cf57e578a026d2789", "v0.6"),
+    ("0x0000000071727de22e5e9d8baf0edac6f37da032", "v0.7"),
+];
+
+/// An ERC-4337 user operation bundled into the transaction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EVMUserOperation {
+    pub entry_point: String,
+    /// `v0.6` or `v0.7`, `None` for any other contract emitting the same event. Those
+    /// operations are listed but not trusted for transfers or gas attribution
+    pub entry_point_version: Option<String>,
+    pub user_op_hash: String,
+    /// Smart account that executed the operation
+    pub sender: String,
+    /// `None` when the account paid for its own gas
+    pub paymaster: Option<String>,
+    /// Paymaster when there is one, the smart account otherwise
+    pub fee_payer: String,
+    pub nonce: U256,
+    pub success: bool,
+    /// Gas cost charged to the fee payer, in wei
+    pub actual_gas_cost: U256,
+    pub actual_gas_used: U256,
+    /// Hex encoded revert data of the account call, for failed operations
+    pub revert_reason: Option<String>,
+    pub log_index: Option<U256>,
+}
+
+impl EVMUserOperation {
+    /// Whether the operation was logged by a known EntryPoint deployment
+    pub fn is_from_entry_point(&self) -> bool {
+        self.entry_point_version.is_some()
+    }
+}
+
+impl EVMChainManager {
+    /// User operations from the `UserOperationEvent` logs of EntryPoint v0.6 and v0.7
+    pub(super) fn extract_user_operations(
+        &self,
+        receipt: &TransactionReceipt,
+    ) -> Vec<EVMUserOperation> {
+        let user_operation = H256::from_str(USER_OPERATION_EVENT_SIGNATURE).unwrap();
+        let revert_reason = H256::from_str(USER_OPERATION_REVERT_REASON_EVENT_SIGNATURE).unwrap();
+
+        let mut operations = Vec::new();
+        for log in &receipt.logs {
+            if log.topics.len() < 4 || log.topics[0] != user_operation || log.data.len() < 128 {
+                continue;
+            }
+            let word = |offset: usize| U256::from_big_endian(&log.data[offset..offset + 32]);
+            let entry_point = format!("{:?}", log.address);
+            let sender = format!("{:?}", H160::from(log.topics[2]));
+            let paymaster = Some(H160::from(log.topics[3]))
+                .filter(|paymaster| !paymaster.is_zero())
+                .map(|paymaster| format!("{:?}", paymaster));
+
+            // The revert reason is logged by the same EntryPoint before the operation event
+            let reason = receipt
+                .logs
+                .iter()
+                .find(|reason| {
+                    reason.address == log.address
+                        && reason.topics.len() >= 2
+                        && reason.topics[0] == revert_reason
+                        && reason.topics[1] == log.topics[1]
+                        && reason.data.len() >= 64
+                })
+                .and_then(|reason| {
+                    let offset = U256::from_big_endian(&reason.data[32..64]).low_u64() as usize;
+                    abi_bytes(&reason.data, offset)
+                })
+                .map(|reason| format!("0x{}", hex::encode(reason)));
+
+            operations.push(EVMUserOperation {
+                entry_point_version: ENTRY_POINTS
+                    .iter()
+                    .find(|(address, _)| *address == entry_point)
+                    .map(|(_, version)| version.to_string()),
+                entry_point,
+                user_op_hash: format!("{:?}", log.topics[1]),
+                fee_payer: paymaster.clone().unwrap_or_else(|| sender.clone()),
+                sender,
+                paymaster,
+                nonce: word(0),
+                success: !word(32).is_zero(),
+                actual_gas_cost: word(64),
+                actual_gas_used: word(96),
+                revert_reason: reason,
+                log_index: log.log_index,
+            });
+        }
+
+        operations
+    }
+}
@@ -0,0 +1,113 @@
+use super::{abi_bytes, EVMChainManager};
+use crate::wormhole::{self, WormholeMessage, WormholeMessageKind};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use std::str::FromStr;
+
+/// LogMessagePublished(address indexed sender, uint64 sequence, uint32 nonce, bytes payload, uint8 consistencyLevel)
+const LOG_MESSAGE_PUBLISHED_EVENT_SIGNATURE: &str =
+    "0x6eb224fb001ed210e379b335e35efe88672a8ce935d981a6896b27ffdf52a3b2";
+/// messageFee() of the core bridge
+const MESSAGE_FEE_SELECTOR: [u8; 4] = [0x1a, 0x90, 0xa2, 0x19];
+
+impl EVMChainManager {
+    /// Messages published by the core bridge logs and VAAs passed in the calldata
+    pub(super) fn extract_wormhole_messages(
+        &self,
+        chain_name: &str,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+    ) -> Vec<WormholeMessage> {
+        let mut messages = Vec::new();
+
+        // Published messages need the chain id, which is implied by the chain, and are only
+        // genuine when logged by the chain's core bridge
+        let published = H256::from_str(LOG_MESSAGE_PUBLISHED_EVENT_SIGNATURE).unwrap();
+        let emitter_chain = wormhole::chain_id(chain_name);
+        let core_bridge = wormhole::core_bridge(chain_name);
+        for log in receipt.iter().flat_map(|receipt| &receipt.logs) {
+            let (Some(emitter_chain), Some(core_bridge)) = (emitter_chain, core_bridge) else {
+                break;
+            };
+            if log.topics.len() < 2
+                || log.topics[0] != published
+                || log.data.len() < 128
+                || format!("{:?}", log.address) != core_bridge
+            {
+                continue;
+            }
+            let word = |offset: usize| U256::from_big_endian(&log.data[offset..offset + 32]);
+            let Some(payload) = abi_bytes(&log.data, word(64).low_u64() as usize) else {
+                continue;
+            };
+
+            messages.push(wormhole::message(
+                WormholeMessageKind::Published,
+                Some(format!("{:?}", log.address)),
+                emitter_chain,
+                log.topics[1].as_bytes(),
+                Some(word(0).low_u64()),
+                word(32).low_u32(),
+                word(96).low_u32() as u8,
+                payload,
+            ));
+        }
+
+        // Redemptions pass the signed VAA as a `bytes` argument
+        let args = tx.input.get(4..).unwrap_or_default();
+        for offset in (0..args.len().saturating_sub(31)).step_by(32) {
+            let pointer = U256::from_big_endian(&args[offset..offset + 32]);
+            if pointer > U256::from(args.len()) || !pointer.low_u64().is_multiple_of(32) {
+                continue;
+            }
+            let Some(vaa) =
+                abi_bytes(args, pointer.low_u64() as usize).and_then(wormhole::parse_vaa)
+            else {
+                continue;
+            };
+            let duplicate = messages.iter().any(|message| {
+                message.kind == WormholeMessageKind::Redeemed
+                    && message.emitter_chain == vaa.emitter_chain
+                    && message.emitter_address == vaa.emitter_address
+                    && message.sequence == vaa.sequence
+            });
+            if !duplicate {
+                messages.push(vaa);
+            }
+        }
+
+        messages
+    }
+
+    /// Message fee of the core bridge at the transaction's block, for published messages
+    pub(super) async fn fetch_wormhole_message_fees(
+        &self,
+        provider: &Provider<Http>,
+        block_number: Option<U64>,
+        messages: &mut [WormholeMessage],
+    ) -> Result<(), ProviderError> {
+        for message in messages
+            .iter_mut()
+            .filter(|message| message.kind == WormholeMessageKind::Published)
+        {
+            let Some(core_contract) = message
+                .core_contract
+                .as_ref()
+                .and_then(|address| H160::from_str(address).ok())
+            else {
+                continue;
+            };
+            let call: TypedTransaction = TransactionRequest::new()
+                .to(core_contract)
+                .data(MESSAGE_FEE_SELECTOR.to_vec())
+                .into();
+            let fee = provider
+                .call(&call, block_number.map(|number| number.into()))
+                .await?;
+            if fee.len() >= 32 {
+                message.message_fee = Some(U256::from_big_endian(&fee[..32]).low_u64());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use ethers::types::I256;
+use serde::{Deserialize, Serialize};
+
+/// Net balance change of an address over a transaction, in the same shape for EVM and Solana
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub address: String,
+    /// Signed decimal change of the native balance in its smallest unit, fees included
+    pub native_change: String,
+    pub token_changes: Vec<TokenChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenChange {
+    /// Token contract or mint
+    pub token: String,
+    /// Signed decimal change in the token's base units
+    pub change: String,
+}
+
+/// Accumulates changes per address, in the order addresses are first touched
+#[derive(Default)]
+pub(crate) struct BalanceLedger {
+    entries: Vec<LedgerEntry>,
+}
+
+struct LedgerEntry {
+    address: String,
+    native: I256,
+    tokens: Vec<(String, I256)>,
+}
+
+impl BalanceLedger {
+    pub fn native(&mut self, address: &str, change: I256) {
+        self.entry(address).native += change;
+    }
+
+    pub fn token(&mut self, address: &str, token: &str, change: I256) {
+        let tokens = &mut self.entry(address).tokens;
+        match tokens.iter_mut().find(|(existing, _)| existing == token) {
+            Some((_, total)) => *total += change,
+            None => tokens.push((token.to_string(), change)),
+        }
+    }
+
+    /// Move `amount` of the native token, or of `token` when given, between two addresses
+    pub fn transfer(&mut self, token: Option<&str>, from: &str, to: &str, amount: I256) {
+        match token {
+            Some(token) => {
+                self.token(from, token, -amount);
+                self.token(to, token, amount);
+            }
+            None => {
+                self.native(from, -amount);
+                self.native(to, amount);
+            }
+        }
+    }
+
+    fn entry(&mut self, address: &str) -> &mut LedgerEntry {
+        let index = match self
+            .entries
+            .iter()
+            .position(|entry| entry.address == address)
+        {
+            Some(index) => index,
+            None => {
+                self.entries.push(LedgerEntry {
+                    address: address.to_string(),
+                    native: I256::zero(),
+                    tokens: Vec::new(),
+                });
+                self.entries.len() - 1
+            }
+        };
+        &mut self.entries[index]
+    }
+
+    /// Net changes, leaving out tokens and addresses whose changes cancel out, such as routers
+    pub fn into_changes(self) -> Vec<BalanceChange> {
+        self.entries
+            .into_iter()
+            .map(|entry| BalanceChange {
+                address: entry.address,
+                native_change: entry.native.to_string(),
+                token_changes: entry
+                    .tokens
+                    .into_iter()
+                    .filter(|(_, change)| !change.is_zero())
+                    .map(|(token, change)| TokenChange {
+                        token,
+                        change: change.to_string(),
+                    })
+                    .collect(),
+            })
+            .filter(|change| change.native_change != "0" || !change.token_changes.is_empty())
+            .collect()
+    }
+}
@@ -53,16 +53,23 @@
 //! }
 //! ```
 
+pub mod cctp;
 pub mod evm;
+pub mod ledger;
+pub mod mayan;
 pub mod solana;
+pub mod wormhole;
 
 // Re-export commonly used types for convenience
 pub use evm::{
-    ChainConfig, EVMChainManager, TransactionAnalysis, ERC20Transfer, EVMConfig
+    ChainConfig, EVMChainManager, TransactionAnalysis, ERC20Transfer, EVMConfig, EVMTransactionData, EVMSwap, EVMApproval, EVMUserOperation,
+    EVMGasAttribution
 };
+pub use ledger::{BalanceChange, TokenChange};
+pub use mayan::{CrossChainQuery, MayanConfig, MayanManager, MayanOrderCost};
 pub use solana::{
     SolanaChainConfig, SolanaChainManager, SolanaTransactionAnalysis, 
-    SolBalanceChange, TokenBalanceChange, SolanaConfig
+    SolBalanceChange, TokenBalanceChange, SolanaTransfer, SolanaConfig
 };
 
 // Re-export anyhow Result for convenience
@@ -1,17 +1,22 @@
-mod evm;
-mod solana;
-
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
     routing::{get, post},
     Router,
 };
-use clap::Parser;
-use evm::{ChainConfig, EVMChainManager, TransactionAnalysis, EVMConfig};
-use solana::{SolanaChainConfig, SolanaChainManager, SolanaTransactionAnalysis, SolanaConfig};
+use clap::{Parser, Subcommand};
+use costx::evm::{
+    parse_rpc_json, parse_rpc_value, ChainConfig, EVMChainManager, TransactionAnalysis, EVMConfig,
+};
+use costx::cctp::CctpTransferMatch;
+use costx::mayan::{CrossChainQuery, MayanConfig, MayanManager, MayanOrderCost};
+use costx::solana::{
+    AddressCostSummary, AddressHistoryQuery, PriorityFeeEstimate, SolanaChainConfig,
+    SolanaChainManager, SolanaSimulation, SolanaTransactionAnalysis, SolanaConfig,
+};
 use serde::Deserialize;
+use solana_sdk::commitment_config::CommitmentLevel;
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 
@@ -27,6 +32,45 @@ struct Config {
 
     #[command(flatten)]
     solana: SolanaConfig,
+
+    #[command(flatten)]
+    mayan: MayanConfig,
+
+    /// Run a one-off command instead of starting the server
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Analyze a Solana getTransaction JSON dump offline and print the analysis
+    SolanaAnalyzeRaw {
+        /// JSON file, or `-` to read from stdin
+        file: String,
+
+        /// Network the transaction was captured from
+        #[arg(long, default_value = "mainnet")]
+        network: String,
+    },
+
+    /// Analyze EVM transaction, receipt and block JSON files offline and print the analysis
+    EvmAnalyzeRaw {
+        /// Chain the transaction was captured from
+        #[arg(long)]
+        chain: String,
+
+        /// eth_getTransactionByHash JSON file
+        #[arg(long)]
+        transaction: String,
+
+        /// eth_getTransactionReceipt JSON file, omit for pending transactions
+        #[arg(long)]
+        receipt: Option<String>,
+
+        /// eth_getBlockByHash JSON file
+        #[arg(long)]
+        block: Option<String>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -35,10 +79,54 @@ struct TransactionRequest {
     tx_hash: String,
 }
 
+#[derive(Deserialize)]
+struct EVMRawTransactionRequest {
+    chain: String,
+    transaction: serde_json::Value,
+    receipt: Option<serde_json::Value>,
+    block: Option<serde_json::Value>,
+}
+
 #[derive(Deserialize)]
 struct SolanaTransactionRequest {
     network: String,
     signature: String,
+    /// `processed`, `confirmed` (default) or `finalized`
+    commitment: Option<CommitmentLevel>,
+}
+
+#[derive(Deserialize)]
+struct SolanaCommitmentParams {
+    commitment: Option<CommitmentLevel>,
+}
+
+#[derive(Deserialize)]
+struct RawAnalysisParams {
+    network: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SolanaSimulationRequest {
+    network: String,
+    /// Base64 or base58 serialized transaction
+    transaction: String,
+}
+
+#[derive(Deserialize)]
+struct AddressCostsParams {
+    network: Option<String>,
+    before: Option<String>,
+    until: Option<String>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PriorityFeeParams {
+    network: Option<String>,
+    /// Comma separated writable accounts
+    accounts: Option<String>,
 }
 
 // Application state
@@ -46,6 +134,7 @@ struct SolanaTransactionRequest {
 struct AppState {
     evm_manager: Arc<EVMChainManager>,
     solana_manager: Arc<SolanaChainManager>,
+    mayan_manager: Arc<MayanManager>,
 }
 
 #[tokio::main]
@@ -60,16 +149,39 @@ async fn main() {
     let evm_manager = Arc::new(EVMChainManager::new(&config.evm));
     // Initialize Solana chain manager with configuration
     let solana_manager = Arc::new(SolanaChainManager::new(&config.solana));
-    let app_state = AppState { evm_manager, solana_manager };
+    for error in solana_manager.idl_errors() {
+        eprintln!("{}", error);
+    }
+    // Initialize Mayan order manager with configuration
+    let mayan_manager = Arc::new(MayanManager::new(&config.mayan));
+
+    if let Some(command) = config.command {
+        if let Err(e) = run_command(command, &evm_manager, &solana_manager).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let app_state = AppState { evm_manager, solana_manager, mayan_manager };
 
     // Build our application with routes
     let app = Router::new()
         .route("/evm/chains", get(get_supported_chains))
         .route("/evm/analyze/:chain/:tx_hash", get(analyze_transaction))
         .route("/evm/transaction", post(analyze_transaction_post))
+        .route("/evm/analyze-raw", post(analyze_raw_transaction))
         .route("/solana/networks", get(get_supported_solana_networks))
         .route("/solana/analyze/:network/:signature", get(analyze_solana_transaction))
         .route("/solana/transaction", post(analyze_solana_transaction_post))
+        .route("/solana/address/:address/costs", get(analyze_solana_address_costs))
+        .route("/solana/priority-fees", get(estimate_solana_priority_fees))
+        .route("/solana/simulate", post(simulate_solana_transaction))
+        .route("/solana/analyze-raw", post(analyze_solana_raw_transaction))
+        .route("/mayan/order/:chain/:tx_hash", get(analyze_mayan_order))
+        .route("/mayan/order", post(analyze_mayan_order_post))
+        .route("/cctp/match/:chain/:tx_hash", get(match_cctp_transfer))
+        .route("/cctp/match", post(match_cctp_transfer_post))
         .layer(CorsLayer::permissive())
         .with_state(app_state);
 
@@ -81,6 +193,43 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+// Run a CLI subcommand and print its JSON output
+async fn run_command(
+    command: Command,
+    evm_manager: &EVMChainManager,
+    solana_manager: &SolanaChainManager,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::EvmAnalyzeRaw { chain, transaction, receipt, block } => {
+            let transaction = parse_rpc_json(&read_input(&transaction)?)?;
+            let receipt = receipt.map(|file| parse_rpc_json(&read_input(&file)?)).transpose()?;
+            let block = block.map(|file| parse_rpc_json(&read_input(&file)?)).transpose()?;
+            let analysis = evm_manager.analyze_transaction_data(
+                &chain,
+                &transaction,
+                receipt.as_ref(),
+                block.as_ref(),
+            )?;
+            println!("{}", serde_json::to_string_pretty(&analysis)?);
+        }
+        Command::SolanaAnalyzeRaw { file, network } => {
+            let json = read_input(&file)?;
+            let analysis = solana_manager.analyze_raw_transaction(&network, &json).await?;
+            println!("{}", serde_json::to_string_pretty(&analysis)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_input(file: &str) -> std::io::Result<String> {
+    if file == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(file)
+    }
+}
+
 // Get supported chains
 async fn get_supported_chains(State(state): State<AppState>) -> Json<Vec<ChainConfig>> {
     let chains = state.evm_manager.get_supported_chains();
@@ -123,6 +272,32 @@ async fn analyze_transaction_post(
     }
 }
 
+// Analyze EVM transaction, receipt and block JSON without network access
+async fn analyze_raw_transaction(
+    State(state): State<AppState>,
+    Json(payload): Json<EVMRawTransactionRequest>,
+) -> Result<Json<TransactionAnalysis>, StatusCode> {
+    let analysis = (|| {
+        let transaction = parse_rpc_value(payload.transaction)?;
+        let receipt = payload.receipt.map(parse_rpc_value).transpose()?;
+        let block = payload.block.map(parse_rpc_value).transpose()?;
+        state.evm_manager.analyze_transaction_data(
+            &payload.chain,
+            &transaction,
+            receipt.as_ref(),
+            block.as_ref(),
+        )
+    })();
+
+    match analysis {
+        Ok(analysis) => Ok(Json(analysis)),
+        Err(e) => {
+            eprintln!("Error analyzing raw transaction: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
 // Get supported Solana networks
 async fn get_supported_solana_networks(State(state): State<AppState>) -> Json<Vec<SolanaChainConfig>> {
     let networks = state.solana_manager.get_supported_chains();
@@ -132,11 +307,13 @@ async fn get_supported_solana_networks(State(state): State<AppState>) -> Json<Ve
 // Analyze Solana transaction by URL parameters
 async fn analyze_solana_transaction(
     Path((network, signature)): Path<(String, String)>,
+    Query(params): Query<SolanaCommitmentParams>,
     State(state): State<AppState>,
 ) -> Result<Json<SolanaTransactionAnalysis>, StatusCode> {
+    let commitment = params.commitment.unwrap_or(CommitmentLevel::Confirmed);
     match state
         .solana_manager
-        .analyze_transaction(&network, &signature)
+        .analyze_transaction_with_commitment(&network, &signature, commitment)
         .await
     {
         Ok(analysis) => Ok(Json(analysis)),
@@ -152,9 +329,10 @@ async fn analyze_solana_transaction_post(
     State(state): State<AppState>,
     Json(payload): Json<SolanaTransactionRequest>,
 ) -> Result<Json<SolanaTransactionAnalysis>, StatusCode> {
+    let commitment = payload.commitment.unwrap_or(CommitmentLevel::Confirmed);
     match state
         .solana_manager
-        .analyze_transaction(&payload.network, &payload.signature)
+        .analyze_transaction_with_commitment(&payload.network, &payload.signature, commitment)
         .await
     {
         Ok(analysis) => Ok(Json(analysis)),
@@ -164,3 +342,156 @@ async fn analyze_solana_transaction_post(
         }
     }
 }
+
+// Aggregate the costs paid by a Solana address over its transaction history
+async fn analyze_solana_address_costs(
+    Path(address): Path<String>,
+    Query(params): Query<AddressCostsParams>,
+    State(state): State<AppState>,
+) -> Result<Json<AddressCostSummary>, StatusCode> {
+    let network = params.network.unwrap_or_else(|| "mainnet".to_string());
+    let query = AddressHistoryQuery {
+        before: params.before,
+        until: params.until,
+        start_time: params.start_time,
+        end_time: params.end_time,
+        limit: params.limit,
+    };
+
+    match state
+        .solana_manager
+        .analyze_address_costs(&network, &address, &query)
+        .await
+    {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => {
+            eprintln!("Error analyzing Solana address costs: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Estimate the Solana priority fee from recent prioritization fees
+async fn estimate_solana_priority_fees(
+    Query(params): Query<PriorityFeeParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PriorityFeeEstimate>, StatusCode> {
+    let network = params.network.unwrap_or_else(|| "mainnet".to_string());
+    let accounts: Vec<String> = params
+        .accounts
+        .iter()
+        .flat_map(|accounts| accounts.split(','))
+        .map(|account| account.trim().to_string())
+        .filter(|account| !account.is_empty())
+        .collect();
+
+    match state
+        .solana_manager
+        .estimate_priority_fee(&network, &accounts)
+        .await
+    {
+        Ok(estimate) => Ok(Json(estimate)),
+        Err(e) => {
+            eprintln!("Error estimating Solana priority fees: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Simulate an unsent Solana transaction and project its costs
+async fn simulate_solana_transaction(
+    State(state): State<AppState>,
+    Json(payload): Json<SolanaSimulationRequest>,
+) -> Result<Json<SolanaSimulation>, StatusCode> {
+    match state
+        .solana_manager
+        .simulate_transaction(&payload.network, &payload.transaction)
+        .await
+    {
+        Ok(simulation) => Ok(Json(simulation)),
+        Err(e) => {
+            eprintln!("Error simulating Solana transaction: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Analyze a Solana getTransaction JSON dump without network access
+async fn analyze_solana_raw_transaction(
+    Query(params): Query<RawAnalysisParams>,
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<SolanaTransactionAnalysis>, StatusCode> {
+    let network = params.network.unwrap_or_else(|| "mainnet".to_string());
+    match state
+        .solana_manager
+        .analyze_raw_transaction(&network, &body)
+        .await
+    {
+        Ok(analysis) => Ok(Json(analysis)),
+        Err(e) => {
+            eprintln!("Error analyzing raw Solana transaction: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Analyze a Mayan order from its source transaction
+async fn analyze_mayan_order(
+    Path((chain, tx_hash)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<MayanOrderCost>, StatusCode> {
+    let query = CrossChainQuery {
+        source_chain: chain,
+        source_tx: tx_hash,
+        destination_chain: None,
+        destination_tx: None,
+    };
+    analyze_mayan_order_post(State(state), Json(query)).await
+}
+
+// Analyze a Mayan order by POST request, optionally with its destination transaction
+async fn analyze_mayan_order_post(
+    State(state): State<AppState>,
+    Json(payload): Json<CrossChainQuery>,
+) -> Result<Json<MayanOrderCost>, StatusCode> {
+    match state
+        .mayan_manager
+        .analyze_order(&state.evm_manager, &state.solana_manager, &payload)
+        .await
+    {
+        Ok(order) => Ok(Json(order)),
+        Err(e) => {
+            eprintln!("Error analyzing Mayan order: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Pair a CCTP burn with its mint on the destination chain
+async fn match_cctp_transfer(
+    Path((chain, tx_hash)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Json<CctpTransferMatch>, StatusCode> {
+    let query = CrossChainQuery {
+        source_chain: chain,
+        source_tx: tx_hash,
+        destination_chain: None,
+        destination_tx: None,
+    };
+    match_cctp_transfer_post(State(state), Json(query)).await
+}
+
+// Pair a CCTP burn by POST request, optionally with its destination transaction
+async fn match_cctp_transfer_post(
+    State(state): State<AppState>,
+    Json(payload): Json<CrossChainQuery>,
+) -> Result<Json<CctpTransferMatch>, StatusCode> {
+    match costx::cctp::match_transfer(&state.evm_manager, &state.solana_manager, &payload).await {
+        Ok(transfer) => Ok(Json(transfer)),
+        Err(e) => {
+            eprintln!("Error matching CCTP transfer: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::cctp::{CctpTransfer, CctpTransferKind};
+use crate::evm::{EVMChainManager, TransactionAnalysis};
+use crate::solana::{SolanaChainManager, SolanaTransactionAnalysis};
+use crate::wormhole;
+use anyhow::Result;
+use clap::Args;
+use ethers::types::{TransactionReceipt, H256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// OrderCreated(bytes32 key), emitted by the Mayan Swift contract
+const SWIFT_ORDER_CREATED_EVENT_SIGNATURE: &str =
+    "0x918554b6bd6e2895ce6553de5de0e1a69db5289aa0e4fe193a0dcd1f14347477";
+
+/// Mayan MCTP contract, the depositor of the CCTP burns of MCTP orders on EVM chains
+const MCTP_CONTRACT: &str = "875d6d37ec55c8cf220b9e5080717549d8aa8eca";
+
+/// Relayer and protocol fees of an order in the Mayan Explorer, in whole input tokens
+const FEE_FIELDS: &[&str] = &[
+    "swapRelayerFee",
+    "redeemRelayerFee",
+    "refundRelayerFee",
+    "clientRelayerFeeSuccess",
+    "clientRelayerFeeRefund",
+    "mayanFee",
+    "referrerFee",
+];
+
+/// Chain key used for Solana mainnet in cross-chain queries
+pub const SOLANA_CHAIN: &str = "solana";
+
+/// Configuration for cross-chain Mayan order analysis
+#[derive(Debug, Clone, Args)]
+pub struct MayanConfig {
+    /// Mayan Explorer API used to look up orders and their destination transactions
+    #[arg(
+        long,
+        env = "MAYAN_EXPLORER_API_URL",
+        default_value = "https://explorer-api.mayan.finance"
+    )]
+    pub mayan_explorer_api_url: String,
+
+    /// CoinGecko compatible API used to price native gas tokens in USD
+    #[arg(
+        long,
+        env = "PRICE_API_URL",
+        default_value = "https://api.coingecko.com/api/v3"
+    )]
+    pub price_api_url: String,
+}
+
+/// Source transaction of a cross-chain transfer, and optionally its known destination
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrossChainQuery {
+    /// EVM chain key or `solana`
+    pub source_chain: String,
+    pub source_tx: String,
+    /// Looked up from the source transaction when omitted
+    pub destination_chain: Option<String>,
+    pub destination_tx: Option<String>,
+}
+
+/// One side of a cross-chain order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrossChainLeg {
+    pub chain: String,
+    pub transaction: String,
+    pub native_token: String,
+    /// Fee paid by the sender, in whole native tokens. Includes priority fees and tips on Solana
+    pub gas_fee: Option<f64>,
+    pub gas_fee_usd: Option<f64>,
+    pub evm: Option<TransactionAnalysis>,
+    pub solana: Option<SolanaTransactionAnalysis>,
+}
+
+/// Combined cost of a Mayan Swift or MCTP order across its source and destination chains
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MayanOrderCost {
+    /// Swift order hash from the `OrderCreated` event, `<source domain>:<nonce>` of the CCTP
+    /// burn of an MCTP order, or the Mayan Explorer's
+    pub order_id: Option<String>,
+    /// Order state PDA of a Swift order opened on Solana
+    pub order_state: Option<String>,
+    /// Mayan service, e.g. `SWIFT_SWAP` or `MCTP`
+    pub service: Option<String>,
+    pub status: Option<String>,
+    pub source: CrossChainLeg,
+    /// `None` while the order is not fulfilled
+    pub destination: Option<CrossChainLeg>,
+    /// Amounts are in whole tokens, as reported by the Mayan Explorer
+    pub token_in: Option<String>,
+    pub amount_in: Option<f64>,
+    pub amount_in_usd: Option<f64>,
+    pub token_out: Option<String>,
+    pub amount_out: Option<f64>,
+    pub amount_out_usd: Option<f64>,
+    /// Relayer and protocol fees reported by the Mayan Explorer, in whole input tokens
+    pub fees: BTreeMap<String, f64>,
+    /// Input value minus output value plus source gas. The destination gas is paid by the
+    /// relayer and recovered through the fees, so it is reported but not added
+    pub total_cost_usd: Option<f64>,
+}
+
+pub struct MayanManager {
+    client: reqwest::Client,
+    explorer_api_url: String,
+    price_api_url: String,
+}
+
+impl MayanManager {
+    pub fn new(config: &MayanConfig) -> Self {
+        MayanManager {
+            client: reqwest::Client::new(),
+            explorer_api_url: config
+                .mayan_explorer_api_url
+                .trim_end_matches('/')
+                .to_string(),
+            price_api_url: config.price_api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Analyze both sides of a Mayan order and combine their costs
+    pub async fn analyze_order(
+        &self,
+        evm: &EVMChainManager,
+        solana: &SolanaChainManager,
+        query: &CrossChainQuery,
+    ) -> Result<MayanOrderCost> {
+        let (mut source, mut order_id) =
+            analyze_leg(evm, solana, &query.source_chain, &query.source_tx).await?;
+
+        // The explorer knows the destination and the amounts. Without it the source leg is
+        // still reported, with the destination only when it is given
+        let order = match self
+            .fetch_order(order_id.as_deref(), &query.source_tx)
+            .await
+        {
+            Ok(order) => Some(order),
+            Err(e) => {
+                eprintln!("Error fetching Mayan order: {}", e);
+                None
+            }
+        };
+        let field = |names: &[&str]| order.as_ref().and_then(|order| string_field(order, names));
+        let number = |names: &[&str]| order.as_ref().and_then(|order| number_field(order, names));
+
+        if order_id.is_none() {
+            order_id = field(&["orderHash", "orderId"]);
+        }
+        let order_state = source.solana.as_ref().and_then(|analysis| {
+            analysis
+                .mayan_orders
+                .iter()
+                .find_map(|order| order.order_state.clone())
+        });
+
+        let destination_chain = query.destination_chain.clone().or_else(|| {
+            field(&["destChain"])
+                .and_then(|chain| chain.parse().ok())
+                .and_then(wormhole::chain_key)
+                .map(str::to_string)
+        });
+        let destination_tx = query
+            .destination_tx
+            .clone()
+            .or_else(|| field(&["fulfillTxHash", "redeemTxHash"]));
+        let mut destination = match (destination_chain, destination_tx) {
+            (Some(chain), Some(tx)) => Some(analyze_leg(evm, solana, &chain, &tx).await?.0),
+            _ => None,
+        };
+
+        // Gas is paid in native tokens, price them once for both legs
+        let mut tokens: Vec<&str> = vec![&source.native_token];
+        tokens.extend(destination.iter().map(|leg| leg.native_token.as_str()));
+        let prices = self.native_token_prices(&tokens).await.unwrap_or_else(|e| {
+            eprintln!("Error fetching native token prices: {}", e);
+            BTreeMap::new()
+        });
+        for leg in std::iter::once(&mut source).chain(destination.as_mut()) {
+            leg.gas_fee_usd = leg
+                .gas_fee
+                .zip(prices.get(&leg.native_token))
+                .map(|(fee, price)| fee * price);
+        }
+
+        let amount_in = number(&["fromAmount"]);
+        let amount_out = number(&["toAmount"]);
+        let amount_in_usd = amount_in
+            .zip(number(&["fromTokenPrice"]))
+            .map(|(amount, price)| amount * price);
+        let amount_out_usd = amount_out
+            .zip(number(&["toTokenPrice"]))
+            .map(|(amount, price)| amount * price);
+        let total_cost_usd = match (amount_in_usd, amount_out_usd, source.gas_fee_usd) {
+            (Some(amount_in), Some(amount_out), gas) => {
+                Some(amount_in - amount_out + gas.unwrap_or(0.0))
+            }
+            _ => None,
+        };
+
+        let fees = FEE_FIELDS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), number(&[name])?)))
+            .collect();
+
+        Ok(MayanOrderCost {
+            order_id,
+            order_state,
+            service: field(&["service"]),
+            status: field(&["clientStatus", "status"]),
+            source,
+            destination,
+            token_in: field(&["fromTokenAddress"]),
+            amount_in,
+            amount_in_usd,
+            token_out: field(&["toTokenAddress"]),
+            amount_out,
+            amount_out_usd,
+            fees,
+            total_cost_usd,
+        })
+    }
+
+    /// Look up an order in the Mayan Explorer by its id when known, then by its source
+    /// transaction
+    async fn fetch_order(&self, order_id: Option<&str>, source_tx: &str) -> Result<Value> {
+        if let Some(order_id) = order_id {
+            let url = format!("{}/v3/swap/order-id/{}", self.explorer_api_url, order_id);
+            if let Ok(order) = self.fetch_json(&url).await {
+                return Ok(order);
+            }
+        }
+        let url = format!("{}/v3/swap/trx/{}", self.explorer_api_url, source_tx);
+        self.fetch_json(&url)
+            .await
+            .map_err(|e| anyhow::anyhow!("Mayan order not found for {}: {}", source_tx, e))
+    }
+
+    async fn fetch_json(&self, url: &str) -> Result<Value> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("{}", response.status()));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// USD price of each native token symbol
+    async fn native_token_prices(&self, symbols: &[&str]) -> Result<BTreeMap<String, f64>> {
+        let ids: Vec<(&str, &str)> = symbols
+            .iter()
+            .filter_map(|symbol| Some((*symbol, coingecko_id(symbol)?)))
+            .collect();
+        if ids.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+
+        let url = format!(
+            "{}/simple/price?ids={}&vs_currencies=usd",
+            self.price_api_url,
+            ids.iter().map(|(_, id)| *id).collect::<Vec<_>>().join(",")
+        );
+        let response: Value = self.client.get(&url).send().await?.json().await?;
+
+        Ok(ids
+            .into_iter()
+            .filter_map(|(symbol, id)| Some((symbol.to_string(), response[id]["usd"].as_f64()?)))
+            .collect())
+    }
+}
+
+/// Analyze one side of an order, returning the id of the order it opens
+pub(crate) async fn analyze_leg(
+    evm: &EVMChainManager,
+    solana: &SolanaChainManager,
+    chain: &str,
+    transaction: &str,
+) -> Result<(CrossChainLeg, Option<String>)> {
+    if chain == SOLANA_CHAIN {
+        let analysis = solana.analyze_transaction("mainnet", transaction).await?;
+        let order_id = analysis
+            .mayan_orders
+            .iter()
+            .find_map(|order| order.order_id.clone());
+        let leg = CrossChainLeg {
+            chain: chain.to_string(),
+            transaction: transaction.to_string(),
+            native_token: "SOL".to_string(),
+            gas_fee: Some(analysis.cost_summary.total_cost as f64 / 1e9),
+            gas_fee_usd: None,
+            evm: None,
+            solana: Some(analysis),
+        };
+        return Ok((leg, order_id));
+    }
+
+    let native_token = evm
+        .get_chain(chain)
+        .map(|config| config.native_token.clone())
+        .ok_or_else(|| anyhow::anyhow!("Chain not supported: {}", chain))?;
+    let data = evm.fetch_transaction_data(chain, transaction).await?;
+    let analysis = evm.analyze_transaction_data(
+        chain,
+        &data.transaction,
+        data.receipt.as_ref(),
+        data.block.as_ref(),
+    )?;
+    let order_id = data.receipt.as_ref().and_then(swift_order_id).or_else(|| {
+        analysis
+            .cctp_transfers
+            .iter()
+            .find(|transfer| {
+                transfer.kind == CctpTransferKind::Burn
+                    && transfer
+                        .depositor
+                        .as_ref()
+                        .is_some_and(|depositor| depositor.ends_with(MCTP_CONTRACT))
+            })
+            .and_then(mctp_order_id)
+    });
+
+    let leg = CrossChainLeg {
+        chain: chain.to_string(),
+        transaction: transaction.to_string(),
+        native_token,
+        gas_fee: analysis
+            .transaction_fee
+            .and_then(|fee| fee.to_string().parse::<f64>().ok())
+            .map(|fee| fee / 1e18),
+        gas_fee_usd: None,
+        evm: Some(analysis),
+        solana: None,
+    };
+    Ok((leg, order_id))
+}
+
+/// Order hash from the `OrderCreated` event of the Swift contract
+fn swift_order_id(receipt: &TransactionReceipt) -> Option<String> {
+    let order_created = H256::from_str(SWIFT_ORDER_CREATED_EVENT_SIGNATURE).ok()?;
+    receipt
+        .logs
+        .iter()
+        .find(|log| log.topics.first() == Some(&order_created) && log.data.len() >= 32)
+        .map(|log| format!("0x{}", hex::encode(&log.data[..32])))
+}
+
+/// MCTP orders are identified by the CCTP message that carries them
+pub(crate) fn mctp_order_id(burn: &CctpTransfer) -> Option<String> {
+    Some(format!("{}:{}", burn.source_domain, burn.nonce.as_ref()?))
+}
+
+fn coingecko_id(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "ETH" => Some("ethereum"),
+        "SOL" => Some("solana"),
+        "AVAX" => Some("avalanche-2"),
+        "MATIC" | "POL" => Some("polygon-ecosystem-token"),
+        _ => None,
+    }
+}
+
+fn string_field(value: &Value, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| match &value[name] {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn number_field(value: &Value, names: &[&str]) -> Option<f64> {
+    names.iter().find_map(|name| value_f64(&value[name]))
+}
+
+/// Numbers are returned either as JSON numbers or as decimal strings
+fn value_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
@@ -1,10 +1,57 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
-use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    TransactionConfirmationStatus, UiTransactionEncoding,
+};
 use std::{collections::HashMap, str::FromStr};
 use clap::Args;
+use crate::cctp::CctpTransfer;
+use crate::ledger::BalanceChange;
+use crate::wormhole::WormholeMessage;
+
+mod balances;
+mod cctp;
+mod costs;
+mod errors;
+mod history;
+mod idl;
+mod instructions;
+mod logs;
+mod mayan;
+mod priority_fees;
+mod raw;
+mod rent;
+mod simulation;
+mod swaps;
+mod token2022;
+mod transfers;
+mod wormhole;
+mod wsol;
+
+pub use costs::{SolanaCostSummary, SolanaTip, JITO_TIP_ACCOUNTS};
+pub use errors::{
+    ProgramError, ProgramErrorRegistry, ProgramErrorSource, SolanaTransactionFailure,
+};
+pub use history::{
+    AddressCostSummary, AddressHistoryError, AddressHistoryQuery, AddressTransactionCost,
+};
+pub use idl::{AnchorIdl, DecodedAccount, DecodedEvent, DecodedInstruction, EventSource};
+pub use logs::{ComputeUnitBreakdown, ProgramComputeUnits, ProgramInvocation};
+pub use mayan::SolanaMayanOrder;
+pub use priority_fees::PriorityFeeEstimate;
+pub use raw::parse_raw_transaction;
+pub use rent::{RentEvent, RentEventKind, RentPayerSummary};
+pub use simulation::SolanaSimulation;
+pub use swaps::SolanaSwap;
+pub use token2022::{MintExtensions, TokenTransferFee, TransferFeeSchedule};
+pub use transfers::{SolanaTransfer, SolanaTransferKind};
+pub use wsol::{NativeSolChange, TemporaryWrappedSolAccount, WrappedSolSummary};
 
 /// Configuration for Solana networks
 #[derive(Debug, Clone, Args)]
@@ -12,6 +59,31 @@ pub struct SolanaConfig {
     /// Solana Mainnet RPC URL
     #[arg(long, env = "SOLANA_RPC_URL", default_value = "https://api.mainnet-beta.solana.com")]
     pub solana_mainnet_rpc_url: String,
+
+    /// Accounts whose incoming SOL transfers are reported as tips (comma separated)
+    #[arg(
+        long,
+        env = "SOLANA_TIP_ACCOUNTS",
+        value_delimiter = ',',
+        default_values = costs::JITO_TIP_ACCOUNTS
+    )]
+    pub solana_tip_accounts: Vec<String>,
+
+    /// Directory of Anchor IDL JSON files used to decode instructions and events
+    #[arg(long, env = "SOLANA_IDL_DIR")]
+    pub solana_idl_dir: Option<String>,
+
+    /// Number of transactions analyzed concurrently when scanning an address history
+    #[arg(long, env = "SOLANA_HISTORY_CONCURRENCY", default_value = "8")]
+    pub solana_history_concurrency: usize,
+
+    /// Net native SOL and wSOL per wallet and report temporary wSOL accounts
+    #[arg(long, env = "SOLANA_UNIFY_WSOL")]
+    pub solana_unify_wsol: bool,
+
+    /// Guardian or Wormholescan API used to look up the signed VAA of published Wormhole messages
+    #[arg(long, env = "WORMHOLE_VAA_API_URL")]
+    pub solana_wormhole_vaa_api_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,9 +102,44 @@ pub struct SolanaTransactionAnalysis {
     pub transaction_fee: Option<u64>,
     pub sol_balance_changes: Vec<SolBalanceChange>,
     pub token_balance_changes: Vec<TokenBalanceChange>,
+    /// Net SOL and token change per wallet, in the same shape as the EVM balance ledger
+    pub balance_ledger: Vec<BalanceChange>,
     pub transaction_status: String,
+    /// Decoded `meta.err`, present for failed transactions
+    pub failure: Option<SolanaTransactionFailure>,
     pub block_time: Option<i64>,
+    /// `processed`, `confirmed` or `finalized`, from `getSignatureStatuses`
+    pub confirmation_status: Option<String>,
+    /// Blocks confirmed on top of the transaction's slot, `None` once finalized
+    pub confirmations: Option<usize>,
     pub compute_units_consumed: Option<u64>,
+    /// Per-program compute units reconstructed from the log messages
+    pub compute_unit_breakdown: Option<ComputeUnitBreakdown>,
+    pub rent_events: Vec<RentEvent>,
+    pub rent_summary: Vec<RentPayerSummary>,
+    /// SOL and token movements in execution order, including inner instructions
+    pub transfers: Vec<SolanaTransfer>,
+    /// Swap hops through Raydium, Orca and Meteora pools, including those routed by Jupiter
+    pub swaps: Vec<SolanaSwap>,
+    /// Wormhole messages posted by the transaction and VAAs it posted for redemption
+    pub wormhole_messages: Vec<WormholeMessage>,
+    /// Circle CCTP burns and the mints of messages received by the transaction
+    pub cctp_transfers: Vec<CctpTransfer>,
+    /// Mayan Swift and MCTP orders opened by the transaction
+    pub mayan_orders: Vec<SolanaMayanOrder>,
+    /// Native SOL and wSOL netted per wallet, when `solana_unify_wsol` is enabled
+    pub wrapped_sol: Option<WrappedSolSummary>,
+    /// Instructions of programs with a loaded Anchor IDL
+    pub decoded_instructions: Vec<DecodedInstruction>,
+    /// Anchor events from `Program data:` logs and self-CPI event instructions
+    pub events: Vec<DecodedEvent>,
+    /// Token-2022 mints involved in the transaction and their extensions
+    pub token_mints: Vec<MintExtensions>,
+    /// Token-level transfer fees, a separate cost line from the SOL fee
+    pub token_transfer_fees: Vec<TokenTransferFee>,
+    pub tips: Vec<SolanaTip>,
+    /// Fee, priority fee and tips, with rent reported separately
+    pub cost_summary: SolanaCostSummary,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,6 +163,14 @@ pub struct TokenBalanceChange {
 pub struct SolanaChainManager {
     chains: HashMap<String, SolanaChainConfig>,
     clients: HashMap<String, RpcClient>,
+    tip_accounts: Vec<String>,
+    error_registry: ProgramErrorRegistry,
+    idls: HashMap<String, AnchorIdl>,
+    idl_errors: Vec<String>,
+    history_concurrency: usize,
+    unify_wsol: bool,
+    http_client: reqwest::Client,
+    wormhole_vaa_api_url: Option<String>,
 }
 
 impl SolanaChainManager {
@@ -80,7 +195,35 @@ impl SolanaChainManager {
             clients.insert(key.clone(), client);
         }
         
-        SolanaChainManager { chains, clients }
+        let mut manager = SolanaChainManager {
+            chains,
+            clients,
+            tip_accounts: config.solana_tip_accounts.clone(),
+            error_registry: ProgramErrorRegistry::with_known_programs(),
+            idls: HashMap::new(),
+            idl_errors: Vec::new(),
+            history_concurrency: config.solana_history_concurrency,
+            unify_wsol: config.solana_unify_wsol,
+            http_client: reqwest::Client::new(),
+            wormhole_vaa_api_url: config.solana_wormhole_vaa_api_url.clone(),
+        };
+
+        if let Some(dir) = &config.solana_idl_dir {
+            if let Err(e) = manager.load_idl_dir(std::path::Path::new(dir)) {
+                manager.idl_errors.push(format!("Error loading IDLs from {}: {}", dir, e));
+            }
+        }
+
+        manager
+    }
+
+    /// Register custom error codes used to decode failures of the given program
+    pub fn register_program_errors(
+        &mut self,
+        program_id: &str,
+        errors: impl IntoIterator<Item = (u32, String, Option<String>)>,
+    ) {
+        self.error_registry.register(program_id, errors);
     }
 
     pub fn get_supported_chains(&self) -> Vec<&SolanaChainConfig> {
@@ -91,6 +234,20 @@ impl SolanaChainManager {
         &self,
         network: &str,
         signature: &str,
+    ) -> Result<SolanaTransactionAnalysis> {
+        self.analyze_transaction_with_commitment(network, signature, CommitmentLevel::Confirmed)
+            .await
+    }
+
+    /// Analyze a transaction at the given commitment level.
+    ///
+    /// `getTransaction` does not serve `processed` transactions, so `Processed` falls back to
+    /// `Confirmed`; `confirmation_status` always reports how final the transaction actually is.
+    pub async fn analyze_transaction_with_commitment(
+        &self,
+        network: &str,
+        signature: &str,
+        commitment: CommitmentLevel,
     ) -> Result<SolanaTransactionAnalysis> {
         let client = self
             .clients
@@ -108,24 +265,82 @@ impl SolanaChainManager {
             .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))?;
 
         // Get transaction details
+        let commitment = match commitment {
+            CommitmentLevel::Processed => CommitmentConfig::confirmed(),
+            commitment => CommitmentConfig { commitment },
+        };
         let transaction = client.get_transaction_with_config(
             &signature_pubkey,
             solana_client::rpc_config::RpcTransactionConfig {
                 encoding: Some(UiTransactionEncoding::JsonParsed),
-                commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
+                commitment: Some(commitment),
                 max_supported_transaction_version: Some(0),
             },
-        )?;
-
-        self.analyze_transaction_details(signature, &chain_config.network, transaction)
+        )
+        .await?;
+
+        // Report how final the transaction is, for callers waiting on finalized costs
+        let status = client
+            .get_signature_statuses_with_history(&[signature_pubkey])
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .flatten();
+
+        // Token-2022 extensions live on the mint accounts, not in the transaction. They only
+        // refine transfer amounts, so the analysis goes on without them
+        let mint_extensions = self
+            .fetch_mint_extensions(client, &transaction)
             .await
+            .unwrap_or_else(|e| {
+                eprintln!("Error fetching mint extensions: {}", e);
+                Vec::new()
+            });
+
+        let mut analysis = self
+            .analyze_transaction_details(
+                signature,
+                &chain_config.network,
+                transaction,
+                mint_extensions,
+            )
+            .await?;
+        if let Some(status) = status {
+            analysis.confirmation_status = status.confirmation_status.map(|status| {
+                match status {
+                    TransactionConfirmationStatus::Processed => "processed",
+                    TransactionConfirmationStatus::Confirmed => "confirmed",
+                    TransactionConfirmationStatus::Finalized => "finalized",
+                }
+                .to_string()
+            });
+            analysis.confirmations = status.confirmations;
+        }
+
+        // Signed VAAs are only available from the guardians, not from the transaction
+        if let Some(api_url) = &self.wormhole_vaa_api_url {
+            crate::wormhole::fetch_signed_vaas(
+                &self.http_client,
+                api_url,
+                &mut analysis.wormhole_messages,
+            )
+            .await;
+        }
+
+        Ok(analysis)
     }
 
-    async fn analyze_transaction_details(
+    /// Analyze an already fetched transaction without any network access.
+    ///
+    /// `token_mints` carries Token-2022 mint extensions; pass an empty list when they are
+    /// unknown, in which case transfer fees are only reported where instructions state them.
+    pub async fn analyze_transaction_details(
         &self,
         signature: &str,
         network: &str,
         transaction: EncodedConfirmedTransactionWithStatusMeta,
+        token_mints: Vec<MintExtensions>,
     ) -> Result<SolanaTransactionAnalysis> {
         let meta = transaction
             .transaction
@@ -149,8 +364,62 @@ impl SolanaChainManager {
         // Extract token balance changes
         let token_balance_changes = self.extract_token_balance_changes(&transaction)?;
 
+        // Net both per wallet, in the same shape as EVM transactions
+        let balance_ledger = self.balance_ledger(&transaction, &sol_balance_changes);
+
         // Extract compute units consumed
-        let compute_units_consumed = meta.compute_units_consumed.unwrap();
+        let compute_units_consumed: Option<u64> = meta.compute_units_consumed.clone().into();
+        let compute_unit_breakdown = self.extract_compute_unit_breakdown(&transaction);
+
+        // Separate refundable rent from the actual cost of the transaction
+        let instructions = instructions::flatten_instructions(&transaction);
+        let rent_events = self.extract_rent_events(&transaction, &instructions);
+        let rent_summary = self.summarize_rent(&transaction, &rent_events);
+
+        // Decode why the transaction failed, with the logs of the failing instruction
+        let logs = match &meta.log_messages {
+            OptionSerializer::Some(logs) => logs.clone(),
+            _ => Vec::new(),
+        };
+        let failure = meta
+            .err
+            .as_ref()
+            .map(|err| self.decode_failure(err, &instructions, &logs));
+
+        // Decode instructions and events of programs with a known IDL
+        let decoded_instructions = self.decode_instructions(&instructions);
+        let events = self.decode_events(&instructions, &logs);
+
+        // Decode individual transfers, including intermediate hops made through CPIs
+        let mut transfers = self.extract_transfers(&transaction, &instructions);
+        let token_transfer_fees =
+            self.apply_transfer_fees(transaction.slot, &mut transfers, &token_mints);
+
+        // Pair the transfers of each AMM swap into hops, and add hops reported by Jupiter
+        let swaps = self.extract_swaps(&transaction, &instructions, &transfers);
+
+        // Decode Wormhole messages posted through the core bridge and VAAs being redeemed
+        let wormhole_messages = self.extract_wormhole_messages(&instructions, &transfers, &logs);
+
+        // Decode CCTP burns and the mints of received messages
+        let cctp_transfers = self.extract_cctp_transfers(&instructions, &logs);
+        let mayan_orders = self.extract_mayan_orders(&instructions, &cctp_transfers);
+
+        // Temporary wSOL accounts only move SOL around, net them into the owner's balance
+        let wrapped_sol = self.unify_wsol.then(|| {
+            self.unify_wrapped_sol(
+                &transaction,
+                &instructions,
+                &transfers,
+                &rent_events,
+                &rent_summary,
+            )
+        });
+
+        // Tips are a real execution cost on top of the fee
+        let tips = self.extract_tips(&transfers);
+        let cost_summary =
+            self.summarize_costs(transaction_fee, &instructions, &tips, &rent_events);
 
         Ok(SolanaTransactionAnalysis {
             signature: signature.to_string(),
@@ -159,9 +428,28 @@ impl SolanaChainManager {
             transaction_fee: Some(transaction_fee),
             sol_balance_changes,
             token_balance_changes,
+            balance_ledger,
             transaction_status,
+            failure,
             block_time: transaction.block_time,
-            compute_units_consumed: Some(compute_units_consumed),
+            confirmation_status: None,
+            confirmations: None,
+            compute_units_consumed,
+            compute_unit_breakdown,
+            rent_events,
+            rent_summary,
+            transfers,
+            swaps,
+            wormhole_messages,
+            cctp_transfers,
+            mayan_orders,
+            wrapped_sol,
+            decoded_instructions,
+            events,
+            token_mints,
+            token_transfer_fees,
+            tips,
+            cost_summary,
         })
     }
 
@@ -212,13 +500,13 @@ impl SolanaChainManager {
 
             // Create a map of account -> pre balance
             let mut pre_balances_map = HashMap::new();
-            for balance in pre_token_balances.clone().unwrap() {
+            for balance in Option::<Vec<_>>::from(pre_token_balances.clone()).unwrap_or_default() {
                 let key = (balance.account_index, balance.mint.clone());
                 pre_balances_map.insert(key, balance.clone());
             }
 
             // Process post balances and calculate changes
-            for post_balance in post_token_balances.clone().unwrap() {
+            for post_balance in Option::<Vec<_>>::from(post_token_balances.clone()).unwrap_or_default() {
                 let key = (post_balance.account_index, post_balance.mint.clone());
                 if let Some(accounts) = self.get_account_keys(transaction) {
                     if let Some(account) = accounts.get(post_balance.account_index as usize) {
@@ -257,30 +545,11 @@ impl SolanaChainManager {
         &self,
         transaction: &EncodedConfirmedTransactionWithStatusMeta,
     ) -> Option<Vec<Pubkey>> {
-        match &transaction.transaction.transaction {
-            solana_transaction_status::EncodedTransaction::Json(ui_transaction) => {
-                match &ui_transaction.message {
-                    solana_transaction_status::UiMessage::Parsed(parsed_message) => {
-                        return Some(
-                            parsed_message
-                                .account_keys
-                                .iter()
-                                .filter_map(|key| Pubkey::from_str(&key.pubkey).ok())
-                                .collect(),
-                        );
-                    }
-                    solana_transaction_status::UiMessage::Raw(raw_message) => {
-                        return Some(
-                            raw_message
-                                .account_keys
-                                .iter()
-                                .filter_map(|key| Pubkey::from_str(key).ok())
-                                .collect(),
-                        );
-                    }
-                }
-            }
-            _ => return None,
+        let keys = instructions::full_account_keys(transaction);
+        if keys.is_empty() {
+            return None;
         }
+
+        Some(keys.iter().filter_map(|key| Pubkey::from_str(key).ok()).collect())
     }
 }
@@ -0,0 +1,49 @@
+use super::instructions::full_account_keys;
+use super::{SolBalanceChange, SolanaChainManager};
+use crate::ledger::{BalanceChange, BalanceLedger};
+use ethers::types::I256;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+
+impl SolanaChainManager {
+    /// Net SOL and token changes per address, in the same shape as EVM transactions. Token
+    /// accounts are attributed to their owner
+    pub(super) fn balance_ledger(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        sol_balance_changes: &[SolBalanceChange],
+    ) -> Vec<BalanceChange> {
+        let mut ledger = BalanceLedger::default();
+        for change in sol_balance_changes {
+            ledger.native(&change.address, I256::from(change.balance_change));
+        }
+
+        // Closed accounts only appear in the pre balances, new ones in the post balances
+        let account_keys = full_account_keys(transaction);
+        if let Some(meta) = &transaction.transaction.meta {
+            for (balances, sign) in [
+                (&meta.pre_token_balances, I256::minus_one()),
+                (&meta.post_token_balances, I256::one()),
+            ] {
+                let OptionSerializer::Some(balances) = balances else {
+                    continue;
+                };
+                for balance in balances {
+                    let owner = match &balance.owner {
+                        OptionSerializer::Some(owner) => Some(owner),
+                        _ => account_keys.get(balance.account_index as usize),
+                    };
+                    let (Some(owner), Ok(amount)) =
+                        (owner, balance.ui_token_amount.amount.parse::<u64>())
+                    else {
+                        continue;
+                    };
+                    ledger.token(owner, &balance.mint, sign * I256::from(amount));
+                }
+            }
+        }
+
+        ledger.into_changes()
+    }
+}
@@ -0,0 +1,188 @@
+use super::idl::raw_events;
+use super::instructions::FlatInstruction;
+use super::SolanaChainManager;
+use crate::cctp::{self, CctpTransfer, CctpTransferKind, SOLANA_DOMAIN};
+use solana_sdk::bs58;
+
+const MESSAGE_TRANSMITTER_V1_PROGRAM_ID: &str = "CCTPmbSD7gX1bxKPAmg77w8oFzNFpaQiQUWD43TKaecd";
+const TOKEN_MESSENGER_MINTER_V1_PROGRAM_ID: &str = "CCTPiPYPc6AsJuwueEnWgSgucamXDZwBd53dQ11YiKX3";
+const MESSAGE_TRANSMITTER_V2_PROGRAM_ID: &str = "CCTPV2Sm4AdWt5296sk4P66VBZ7bEhcARwFaaS9YPbeC";
+const TOKEN_MESSENGER_MINTER_V2_PROGRAM_ID: &str = "CCTPV2vPZJS2u2BBsUoscuikbYjnpFmbFsvVuJdgUMQe";
+
+/// Anchor discriminators: sha256("event:DepositForBurn"), sha256("event:MintAndWithdraw")
+/// and sha256("global:receive_message")
+const DEPOSIT_FOR_BURN_DISCRIMINATOR: [u8; 8] = [0x90, 0xfc, 0x91, 0x92, 0x06, 0x4a, 0xa7, 0xeb];
+const MINT_AND_WITHDRAW_DISCRIMINATOR: [u8; 8] = [0x4b, 0x43, 0xe5, 0x46, 0xa2, 0x7e, 0x00, 0x47];
+const RECEIVE_MESSAGE_DISCRIMINATOR: [u8; 8] = [0x26, 0x90, 0x7f, 0xe1, 0x1f, 0xe1, 0xee, 0x19];
+
+impl SolanaChainManager {
+    /// Burns from `DepositForBurn` events and mints from `receive_message` instructions
+    /// with their `MintAndWithdraw` events
+    pub(super) fn extract_cctp_transfers(
+        &self,
+        instructions: &[FlatInstruction],
+        logs: &[String],
+    ) -> Vec<CctpTransfer> {
+        let mut transfers = Vec::new();
+        let mut mints = Vec::new();
+        for event in raw_events(instructions, logs) {
+            let version = match event.program_id.as_str() {
+                TOKEN_MESSENGER_MINTER_V1_PROGRAM_ID => 1,
+                TOKEN_MESSENGER_MINTER_V2_PROGRAM_ID => 2,
+                _ => continue,
+            };
+            if let Some(burn) = decode_deposit_for_burn(version, &event.data) {
+                transfers.push(burn);
+            } else if let Some(mint) = decode_mint_and_withdraw(&event.data) {
+                mints.push(mint);
+            }
+        }
+
+        // The transmitter calls the token messenger, which emits one event per received message
+        let mut mints = mints.into_iter();
+        for instruction in instructions.iter().filter(|instruction| {
+            instruction.program_id == MESSAGE_TRANSMITTER_V1_PROGRAM_ID
+                || instruction.program_id == MESSAGE_TRANSMITTER_V2_PROGRAM_ID
+        }) {
+            let Some(mut transfer) = decode_receive_message(&instruction.data) else {
+                continue;
+            };
+            if let Some((mint_token, minted_amount)) = mints.next() {
+                transfer.mint_token = Some(mint_token);
+                transfer.minted_amount = Some(minted_amount.to_string());
+            }
+            transfers.push(transfer);
+        }
+
+        transfers
+    }
+}
+
+/// Borsh `DepositForBurn` event. V1 starts with the nonce, V2 appends the max fee after the
+/// destination caller
+fn decode_deposit_for_burn(version: u32, data: &[u8]) -> Option<CctpTransfer> {
+    let data = data.strip_prefix(&DEPOSIT_FOR_BURN_DISCRIMINATOR)?;
+    let (nonce, data) = if version == 1 {
+        let nonce = u64::from_le_bytes(data.get(0..8)?.try_into().ok()?);
+        (Some(nonce.to_string()), data.get(8..)?)
+    } else {
+        (None, data)
+    };
+    let key = |offset: usize| Some(hex::encode(data.get(offset..offset + 32)?));
+    let u64_at = |offset: usize| {
+        Some(u64::from_le_bytes(
+            data.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+    let destination_domain = u32::from_le_bytes(data.get(104..108)?.try_into().ok()?);
+
+    Some(CctpTransfer {
+        kind: CctpTransferKind::Burn,
+        version,
+        source_domain: SOLANA_DOMAIN,
+        destination_domain,
+        nonce,
+        burn_token: Some(key(0)?),
+        mint_token: None,
+        amount: u64_at(32)?.to_string(),
+        minted_amount: None,
+        depositor: Some(key(40)?),
+        mint_recipient: Some(key(72)?),
+        max_fee: if version == 2 {
+            Some(u64_at(172)?.to_string())
+        } else {
+            None
+        },
+        fee_executed: None,
+    })
+}
+
+/// Borsh `MintAndWithdraw` event: mint recipient, amount and mint
+fn decode_mint_and_withdraw(data: &[u8]) -> Option<(String, u64)> {
+    let data = data.strip_prefix(&MINT_AND_WITHDRAW_DISCRIMINATOR)?;
+    let amount = u64::from_le_bytes(data.get(32..40)?.try_into().ok()?);
+    let mint_token = bs58::encode(data.get(40..72)?).into_string();
+    Some((mint_token, amount))
+}
+
+/// `receive_message` instruction: the message followed by its attestation, both `Vec<u8>`
+fn decode_receive_message(data: &[u8]) -> Option<CctpTransfer> {
+    let message = borsh_bytes(data.strip_prefix(&RECEIVE_MESSAGE_DISCRIMINATOR)?)?;
+    cctp::parse_message(CctpTransferKind::Mint, message)
+}
+
+/// Borsh `Vec<u8>` at the start of the data
+fn borsh_bytes(data: &[u8]) -> Option<&[u8]> {
+    let length = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?) as usize;
+    data.get(4..4 + length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// V1 `DepositForBurn` of 25 USDC from Solana to Ethereum, as emitted by the token
+    /// messenger minter: nonce, burn token, amount, depositor, mint recipient, destination
+    /// domain, destination token messenger and caller
+    const DEPOSIT_FOR_BURN_EVENT: &str = "90fc9192064aa7ebd425040000000000c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d6140787d010000000001010101010101010101010101010101010101010101010101010101010101010000000000000000000000008ba1f109551bd432803012645ac136ddd64dba7200000000000000000000000000000000bd3fa81b58ba92a82136038b25adec7066af31550000000000000000000000000000000000000000000000000000000000000000";
+
+    /// V1 `receive_message` of a 10 USDC burn from Ethereum to Solana, followed by its
+    /// 130-byte attestation
+    const RECEIVE_MESSAGE_INSTRUCTION: &str = "26907fe11fe1ee19f8000000000000000000000000000005000000000004cb2f000000000000000000000000bd3fa81b58ba92a82136038b25adec7066af3155a65fc943419a5ad590042fd67c9791fd015acf53a54cc823edb8ff81b9ed722e000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb484157b0580f31c5fce44a62582dbcf9d78ee75943a084a393b350368d2289930800000000000000000000000000000000000000000000000000000000009896800000000000000000000000008ba1f109551bd432803012645ac136ddd64dba728200000011111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111";
+
+    #[test]
+    fn discriminators_match_anchor_sighash() {
+        use super::super::idl::sighash;
+        assert_eq!(
+            DEPOSIT_FOR_BURN_DISCRIMINATOR.to_vec(),
+            sighash("event", "DepositForBurn")
+        );
+        assert_eq!(
+            MINT_AND_WITHDRAW_DISCRIMINATOR.to_vec(),
+            sighash("event", "MintAndWithdraw")
+        );
+        assert_eq!(
+            RECEIVE_MESSAGE_DISCRIMINATOR.to_vec(),
+            sighash("global", "receive_message")
+        );
+    }
+
+    #[test]
+    fn decodes_deposit_for_burn_event() {
+        let data = hex::decode(DEPOSIT_FOR_BURN_EVENT).unwrap();
+        let transfer = decode_deposit_for_burn(1, &data).unwrap();
+
+        assert_eq!(transfer.kind, CctpTransferKind::Burn);
+        assert_eq!(transfer.source_domain, SOLANA_DOMAIN);
+        assert_eq!(transfer.destination_domain, 0);
+        assert_eq!(transfer.nonce.as_deref(), Some("271828"));
+        assert_eq!(transfer.amount, "25000000");
+        // USDC mint EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v
+        assert_eq!(
+            transfer.burn_token.as_deref(),
+            Some("c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d61")
+        );
+        assert_eq!(
+            transfer.mint_recipient.as_deref(),
+            Some("0000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72")
+        );
+        assert_eq!(transfer.max_fee, None);
+    }
+
+    #[test]
+    fn decodes_receive_message_instruction() {
+        let data = hex::decode(RECEIVE_MESSAGE_INSTRUCTION).unwrap();
+        let transfer = decode_receive_message(&data).unwrap();
+
+        assert_eq!(transfer.kind, CctpTransferKind::Mint);
+        assert_eq!(transfer.version, 1);
+        assert_eq!(transfer.source_domain, 0);
+        assert_eq!(transfer.destination_domain, SOLANA_DOMAIN);
+        assert_eq!(transfer.nonce.as_deref(), Some("314159"));
+        assert_eq!(transfer.amount, "10000000");
+        assert_eq!(
+            transfer.burn_token.as_deref(),
+            Some("000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use super::instructions::{FlatInstruction, COMPUTE_BUDGET_PROGRAM_ID};
+use super::rent::rent_totals;
+use super::{RentEvent, SolanaChainManager, SolanaTransfer, SolanaTransferKind};
+use serde::{Deserialize, Serialize};
+
+/// Default tip accounts of the Jito block engine
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+/// Compute unit limit applied per instruction when no `SetComputeUnitLimit` is present
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// SOL sent to a block engine tip account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaTip {
+    pub tip_account: String,
+    pub payer: String,
+    pub lamports: u64,
+    pub instruction_index: usize,
+}
+
+/// Execution cost of a transaction, with refundable rent kept apart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaCostSummary {
+    pub base_fee: u64,
+    pub priority_fee: u64,
+    /// Compute unit price in micro-lamports, from `SetComputeUnitPrice`
+    pub compute_unit_price: Option<u64>,
+    /// Requested compute unit limit, explicit or the runtime default
+    pub compute_unit_limit: u64,
+    pub tips: u64,
+    /// Base fee plus priority fee plus tips
+    pub total_cost: u64,
+    pub rent_deposited: u64,
+    pub rent_reclaimed: u64,
+}
+
+impl SolanaChainManager {
+    pub(super) fn extract_tips(&self, transfers: &[SolanaTransfer]) -> Vec<SolanaTip> {
+        transfers
+            .iter()
+            .filter(|transfer| transfer.kind == SolanaTransferKind::SystemTransfer)
+            .filter_map(|transfer| {
+                let destination = transfer.destination.as_ref()?;
+                if !self.tip_accounts.contains(destination) {
+                    return None;
+                }
+
+                Some(SolanaTip {
+                    tip_account: destination.clone(),
+                    payer: transfer.source.clone().unwrap_or_default(),
+                    lamports: transfer.amount,
+                    instruction_index: transfer.instruction_index,
+                })
+            })
+            .collect()
+    }
+
+    pub(super) fn summarize_costs(
+        &self,
+        fee: u64,
+        instructions: &[FlatInstruction],
+        tips: &[SolanaTip],
+        rent_events: &[RentEvent],
+    ) -> SolanaCostSummary {
+        let mut compute_unit_price = None;
+        let mut compute_unit_limit = None;
+        let mut other_top_level_instructions = 0u64;
+
+        for instruction in instructions.iter().filter(|i| i.inner_index.is_none()) {
+            if instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+                other_top_level_instructions += 1;
+                continue;
+            }
+
+            match instruction.data.split_first() {
+                Some((2, limit)) if limit.len() >= 4 => {
+                    compute_unit_limit =
+                        Some(u32::from_le_bytes(limit[..4].try_into().unwrap()) as u64);
+                }
+                Some((3, price)) if price.len() >= 8 => {
+                    compute_unit_price = Some(u64::from_le_bytes(price[..8].try_into().unwrap()));
+                }
+                _ => {}
+            }
+        }
+
+        let compute_unit_limit = compute_unit_limit
+            .unwrap_or(other_top_level_instructions * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+
+        // The priority fee is the compute unit price times the requested limit, rounded up
+        let priority_fee = compute_unit_price
+            .map(|price| {
+                let micro_lamports = price as u128 * compute_unit_limit as u128;
+                micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64
+            })
+            .unwrap_or(0)
+            .min(fee);
+        let base_fee = fee - priority_fee;
+
+        let tips: u64 = tips.iter().map(|tip| tip.lamports).sum();
+
+        let (rent_deposited, rent_reclaimed) = rent_totals(rent_events);
+
+        SolanaCostSummary {
+            base_fee,
+            priority_fee,
+            compute_unit_price,
+            compute_unit_limit,
+            tips,
+            total_cost: fee + tips,
+            rent_deposited,
+            rent_reclaimed,
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use super::instructions::{
+    FlatInstruction, ASSOCIATED_TOKEN_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+};
+use super::SolanaChainManager;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use std::collections::HashMap;
+
+pub(crate) const JUPITER_V6_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+/// Anchor framework errors, shared by every Anchor program
+const ANCHOR_ERRORS: &[(u32, &str)] = &[
+    (100, "InstructionMissing"),
+    (101, "InstructionFallbackNotFound"),
+    (102, "InstructionDidNotDeserialize"),
+    (103, "InstructionDidNotSerialize"),
+    (2000, "ConstraintMut"),
+    (2001, "ConstraintHasOne"),
+    (2002, "ConstraintSigner"),
+    (2003, "ConstraintRaw"),
+    (2004, "ConstraintOwner"),
+    (2005, "ConstraintRentExempt"),
+    (2006, "ConstraintSeeds"),
+    (2007, "ConstraintExecutable"),
+    (2009, "ConstraintAssociated"),
+    (2010, "ConstraintAssociatedInit"),
+    (2011, "ConstraintClose"),
+    (2012, "ConstraintAddress"),
+    (2013, "ConstraintZero"),
+    (2014, "ConstraintTokenMint"),
+    (2015, "ConstraintTokenOwner"),
+    (2016, "ConstraintMintMintAuthority"),
+    (2017, "ConstraintMintFreezeAuthority"),
+    (2018, "ConstraintMintDecimals"),
+    (2019, "ConstraintSpace"),
+    (2020, "ConstraintAccountIsNone"),
+    (2500, "RequireViolated"),
+    (2501, "RequireEqViolated"),
+    (2502, "RequireKeysEqViolated"),
+    (2503, "RequireNeqViolated"),
+    (2504, "RequireKeysNeqViolated"),
+    (2505, "RequireGtViolated"),
+    (2506, "RequireGteViolated"),
+    (3000, "AccountDiscriminatorAlreadySet"),
+    (3001, "AccountDiscriminatorNotFound"),
+    (3002, "AccountDiscriminatorMismatch"),
+    (3003, "AccountDidNotDeserialize"),
+    (3004, "AccountDidNotSerialize"),
+    (3005, "AccountNotEnoughKeys"),
+    (3006, "AccountNotMutable"),
+    (3007, "AccountOwnedByWrongProgram"),
+    (3008, "InvalidProgramId"),
+    (3009, "InvalidProgramExecutable"),
+    (3010, "AccountNotSigner"),
+    (3011, "AccountNotSystemOwned"),
+    (3012, "AccountNotInitialized"),
+    (3013, "AccountNotProgramData"),
+    (3014, "AccountNotAssociatedTokenAccount"),
+    (3015, "AccountSysvarMismatch"),
+    (3016, "AccountReallocExceedsLimit"),
+    (3017, "AccountDuplicateReallocs"),
+    (4100, "DeclaredProgramIdMismatch"),
+    (5000, "Deprecated"),
+];
+
+const SYSTEM_ERRORS: &[(u32, &str)] = &[
+    (0, "AccountAlreadyInUse"),
+    (1, "ResultWithNegativeLamports"),
+    (2, "InvalidProgramId"),
+    (3, "InvalidAccountDataLength"),
+    (4, "MaxSeedLengthExceeded"),
+    (5, "AddressWithSeedMismatch"),
+    (6, "NonceNoRecentBlockhashes"),
+    (7, "NonceBlockhashNotExpired"),
+    (8, "NonceUnexpectedBlockhashValue"),
+];
+
+const TOKEN_ERRORS: &[(u32, &str)] = &[
+    (0, "NotRentExempt"),
+    (1, "InsufficientFunds"),
+    (2, "InvalidMint"),
+    (3, "MintMismatch"),
+    (4, "OwnerMismatch"),
+    (5, "FixedSupply"),
+    (6, "AlreadyInUse"),
+    (7, "InvalidNumberOfProvidedSigners"),
+    (8, "InvalidNumberOfRequiredSigners"),
+    (9, "UninitializedState"),
+    (10, "NativeNotSupported"),
+    (11, "NonNativeHasBalance"),
+    (12, "InvalidInstruction"),
+    (13, "InvalidState"),
+    (14, "Overflow"),
+    (15, "AuthorityTypeNotSupported"),
+    (16, "MintCannotFreeze"),
+    (17, "AccountFrozen"),
+    (18, "MintDecimalsMismatch"),
+    (19, "NonNativeNotSupported"),
+];
+
+const ASSOCIATED_TOKEN_ERRORS: &[(u32, &str)] = &[(0, "InvalidOwner")];
+
+const JUPITER_V6_ERRORS: &[(u32, &str)] = &[
+    (6000, "EmptyRoute"),
+    (6001, "SlippageToleranceExceeded"),
+    (6002, "InvalidCalculation"),
+    (6003, "MissingPlatformFeeAccount"),
+    (6004, "InvalidSlippage"),
+    (6005, "NotEnoughPercent"),
+    (6006, "InvalidInputIndex"),
+    (6007, "InvalidOutputIndex"),
+    (6008, "NotEnoughAccountKeys"),
+    (6009, "NonZeroMinimumOutAmountNotSupported"),
+    (6010, "InvalidRoutePlan"),
+    (6011, "InvalidReferralAuthority"),
+    (6012, "LedgerTokenAccountDoesNotMatch"),
+    (6013, "InvalidTokenLedger"),
+    (6014, "IncorrectTokenProgramID"),
+    (6015, "TokenProgramNotProvided"),
+    (6016, "SwapNotSupported"),
+    (6017, "ExactOutAmountNotMatched"),
+    (6018, "SourceAndDestinationMintCannotBeTheSame"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProgramErrorSource {
+    /// Error registered for the failing program
+    Program,
+    /// Anchor framework error range
+    Anchor,
+    /// Only found in an `AnchorError` log line
+    Log,
+}
+
+/// A custom program error code resolved to a name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramError {
+    pub name: String,
+    pub message: Option<String>,
+    pub source: ProgramErrorSource,
+}
+
+/// Custom error codes per program, plus the Anchor framework ranges
+#[derive(Debug, Clone, Default)]
+pub struct ProgramErrorRegistry {
+    programs: HashMap<String, HashMap<u32, (String, Option<String>)>>,
+}
+
+impl ProgramErrorRegistry {
+    /// Registry with the errors of the System, SPL Token, ATA and Jupiter programs
+    pub fn with_known_programs() -> Self {
+        let mut registry = Self::default();
+        for (program_id, errors) in [
+            (SYSTEM_PROGRAM_ID, SYSTEM_ERRORS),
+            (TOKEN_PROGRAM_ID, TOKEN_ERRORS),
+            (TOKEN_2022_PROGRAM_ID, TOKEN_ERRORS),
+            (ASSOCIATED_TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_ERRORS),
+            (JUPITER_V6_PROGRAM_ID, JUPITER_V6_ERRORS),
+        ] {
+            registry.register(
+                program_id,
+                errors
+                    .iter()
+                    .map(|(code, name)| (*code, name.to_string(), None)),
+            );
+        }
+        registry
+    }
+
+    /// Register `(code, name, message)` entries for a program
+    pub fn register(
+        &mut self,
+        program_id: &str,
+        errors: impl IntoIterator<Item = (u32, String, Option<String>)>,
+    ) {
+        let entries = self.programs.entry(program_id.to_string()).or_default();
+        for (code, name, message) in errors {
+            entries.insert(code, (name, message));
+        }
+    }
+
+    /// Decode a custom error of a program. Codes below 6000 of other programs would collide
+    /// with Anchor's, so they are only read as Anchor errors when the program is known to be
+    /// built with Anchor
+    pub fn decode(&self, program_id: &str, code: u32, anchor: bool) -> Option<ProgramError> {
+        if let Some((name, message)) = self.programs.get(program_id).and_then(|e| e.get(&code)) {
+            return Some(ProgramError {
+                name: name.clone(),
+                message: message.clone(),
+                source: ProgramErrorSource::Program,
+            });
+        }
+
+        // Anchor reserves everything below 6000 for framework errors
+        if anchor && code < 6000 {
+            if let Some((_, name)) = ANCHOR_ERRORS.iter().find(|(c, _)| *c == code) {
+                return Some(ProgramError {
+                    name: name.to_string(),
+                    message: None,
+                    source: ProgramErrorSource::Anchor,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Structured description of why a transaction failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaTransactionFailure {
+    /// The raw `TransactionError`, e.g. `InstructionError(2, Custom(6001))`
+    pub error: String,
+    pub instruction_index: Option<u8>,
+    /// Program of the failed top-level instruction
+    pub instruction_program_id: Option<String>,
+    /// Innermost program that reported the failure in the logs
+    pub program_id: Option<String>,
+    /// `InstructionError` variant, e.g. `Custom` or `InsufficientFunds`
+    pub instruction_error: Option<String>,
+    pub custom_error_code: Option<u32>,
+    pub decoded_error: Option<ProgramError>,
+    /// Log lines of the failed instruction
+    pub logs: Vec<String>,
+}
+
+impl SolanaChainManager {
+    pub(super) fn decode_failure(
+        &self,
+        error: &TransactionError,
+        instructions: &[FlatInstruction],
+        logs: &[String],
+    ) -> SolanaTransactionFailure {
+        let (instruction_index, instruction_error) = match error {
+            TransactionError::InstructionError(index, instruction_error) => {
+                (Some(*index), Some(instruction_error))
+            }
+            _ => (None, None),
+        };
+
+        let instruction_program_id = instruction_index.and_then(|index| {
+            instructions
+                .iter()
+                .find(|i| i.inner_index.is_none() && i.outer_index == index as usize)
+                .map(|i| i.program_id.clone())
+        });
+
+        let logs = instruction_logs(logs, instruction_index);
+
+        // The first "failed" line comes from the innermost program on the stack
+        let program_id = logs
+            .iter()
+            .filter_map(|line| line.strip_prefix("Program "))
+            .find_map(|rest| {
+                let (program_id, event) = rest.split_once(' ')?;
+                event.starts_with("failed").then(|| program_id.to_string())
+            })
+            .or_else(|| instruction_program_id.clone());
+
+        let custom_error_code = match instruction_error {
+            Some(InstructionError::Custom(code)) => Some(*code),
+            _ => None,
+        };
+
+        // A loaded IDL or an `AnchorError` line tells that the failing program uses Anchor
+        let anchor = program_id
+            .as_ref()
+            .is_some_and(|program_id| self.idls.contains_key(program_id))
+            || logs.iter().any(|line| line.contains("AnchorError"));
+        let decoded_error = custom_error_code.and_then(|code| {
+            program_id
+                .as_deref()
+                .and_then(|program_id| self.error_registry.decode(program_id, code, anchor))
+                .or_else(|| anchor_error_from_logs(&logs))
+        });
+
+        SolanaTransactionFailure {
+            error: format!("{:?}", error),
+            instruction_index,
+            instruction_program_id,
+            program_id,
+            instruction_error: instruction_error.map(|e| {
+                let debug = format!("{:?}", e);
+                debug.split('(').next().unwrap_or_default().to_string()
+            }),
+            custom_error_code,
+            decoded_error,
+            logs,
+        }
+    }
+}
+
+/// Log lines from the start of the given top-level instruction to the end of the logs
+fn instruction_logs(logs: &[String], instruction_index: Option<u8>) -> Vec<String> {
+    let Some(index) = instruction_index else {
+        return logs.to_vec();
+    };
+
+    let start = logs
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("Program ") && line.ends_with(" invoke [1]"))
+        .nth(index as usize)
+        .map(|(position, _)| position);
+
+    match start {
+        Some(start) => logs[start..].to_vec(),
+        None => logs.to_vec(),
+    }
+}
+
+/// Parse "AnchorError ... Error Code: Name. Error Number: N. Error Message: Text." lines
+fn anchor_error_from_logs(logs: &[String]) -> Option<ProgramError> {
+    let line = logs.iter().find(|line| line.contains("AnchorError"))?;
+    let name = line
+        .split("Error Code: ")
+        .nth(1)?
+        .split('.')
+        .next()?
+        .to_string();
+    let message = line
+        .split("Error Message: ")
+        .nth(1)
+        .map(|message| message.trim_end_matches('.').to_string());
+
+    Some(ProgramError {
+        name,
+        message,
+        source: ProgramErrorSource::Log,
+    })
+}
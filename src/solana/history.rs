@@ -0,0 +1,275 @@
+use super::rent::rent_totals;
+use super::{SolanaChainManager, SolanaTransactionAnalysis};
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::str::FromStr;
+
+/// Number of transactions analyzed when the query sets no limit
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+const MAX_HISTORY_LIMIT: usize = 1_000;
+/// Largest page `getSignaturesForAddress` returns
+const SIGNATURES_PAGE_SIZE: usize = 1_000;
+
+/// Range of an address history scan; signatures are walked from newest to oldest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressHistoryQuery {
+    /// Start below this signature (exclusive), e.g. `next_before` of a previous scan
+    pub before: Option<String>,
+    /// Stop at this signature (exclusive)
+    pub until: Option<String>,
+    /// Skip transactions older than this Unix timestamp
+    pub start_time: Option<i64>,
+    /// Skip transactions newer than this Unix timestamp
+    pub end_time: Option<i64>,
+    /// Maximum number of transactions to analyze, at most 1000
+    pub limit: Option<usize>,
+}
+
+/// Costs of a single transaction attributed to the scanned address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressTransactionCost {
+    pub signature: String,
+    pub slot: Option<u64>,
+    pub block_time: Option<i64>,
+    pub success: bool,
+    /// Fee and priority fee are only attributed when the address paid them
+    pub fee_payer: bool,
+    pub fee: u64,
+    pub priority_fee: u64,
+    pub tips: u64,
+    pub rent_deposited: u64,
+    pub rent_reclaimed: u64,
+    /// Fee plus tips paid by the address
+    pub total_cost: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressHistoryError {
+    pub signature: String,
+    pub error: String,
+}
+
+/// Aggregated costs of an address over a range of its transaction history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressCostSummary {
+    pub address: String,
+    pub network: String,
+    pub transaction_count: usize,
+    pub failed_transaction_count: usize,
+    pub fees: u64,
+    pub priority_fees: u64,
+    /// Fees spent on transactions that failed
+    pub failed_transaction_fees: u64,
+    pub tips: u64,
+    pub rent_deposited: u64,
+    pub rent_reclaimed: u64,
+    /// Fees plus tips
+    pub total_cost: u64,
+    pub first_block_time: Option<i64>,
+    pub last_block_time: Option<i64>,
+    /// Pass as `before` to continue the scan, set when the limit was reached
+    pub next_before: Option<String>,
+    pub transactions: Vec<AddressTransactionCost>,
+    /// Signatures that could not be analyzed
+    pub errors: Vec<AddressHistoryError>,
+}
+
+impl SolanaChainManager {
+    /// Analyze the transactions of an address and aggregate what it paid for them
+    pub async fn analyze_address_costs(
+        &self,
+        network: &str,
+        address: &str,
+        query: &AddressHistoryQuery,
+    ) -> Result<AddressCostSummary> {
+        let (signatures, next_before) = self.fetch_signatures(network, address, query).await?;
+
+        // Analyze with bounded concurrency, keeping the newest-first order
+        let results: Vec<(String, Result<SolanaTransactionAnalysis>)> = stream::iter(signatures)
+            .map(|signature| async move {
+                let analysis = self.analyze_transaction(network, &signature).await;
+                (signature, analysis)
+            })
+            .buffered(self.history_concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut summary = AddressCostSummary {
+            address: address.to_string(),
+            network: network.to_string(),
+            transaction_count: 0,
+            failed_transaction_count: 0,
+            fees: 0,
+            priority_fees: 0,
+            failed_transaction_fees: 0,
+            tips: 0,
+            rent_deposited: 0,
+            rent_reclaimed: 0,
+            total_cost: 0,
+            first_block_time: None,
+            last_block_time: None,
+            next_before,
+            transactions: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        for (signature, analysis) in results {
+            let analysis = match analysis {
+                Ok(analysis) => analysis,
+                Err(e) => {
+                    summary.errors.push(AddressHistoryError {
+                        signature,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let cost = address_transaction_cost(address, &analysis);
+
+            summary.transaction_count += 1;
+            summary.fees += cost.fee;
+            summary.priority_fees += cost.priority_fee;
+            summary.tips += cost.tips;
+            summary.rent_deposited += cost.rent_deposited;
+            summary.rent_reclaimed += cost.rent_reclaimed;
+            summary.total_cost += cost.total_cost;
+            if !cost.success {
+                summary.failed_transaction_count += 1;
+                summary.failed_transaction_fees += cost.fee;
+            }
+            if let Some(block_time) = cost.block_time {
+                summary.first_block_time = Some(
+                    summary
+                        .first_block_time
+                        .map_or(block_time, |t| t.min(block_time)),
+                );
+                summary.last_block_time = Some(
+                    summary
+                        .last_block_time
+                        .map_or(block_time, |t| t.max(block_time)),
+                );
+            }
+
+            summary.transactions.push(cost);
+        }
+
+        Ok(summary)
+    }
+
+    /// Page through `getSignaturesForAddress` until the query range or limit is exhausted
+    async fn fetch_signatures(
+        &self,
+        network: &str,
+        address: &str,
+        query: &AddressHistoryQuery,
+    ) -> Result<(Vec<String>, Option<String>)> {
+        let client = self
+            .clients
+            .get(network)
+            .ok_or_else(|| anyhow::anyhow!("Network not supported: {}", network))?;
+
+        let address =
+            Pubkey::from_str(address).map_err(|e| anyhow::anyhow!("Invalid address: {}", e))?;
+        let parse_signature = |signature: &Option<String>| {
+            signature
+                .as_deref()
+                .map(Signature::from_str)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))
+        };
+        let mut before = parse_signature(&query.before)?;
+        let until = parse_signature(&query.until)?;
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .min(MAX_HISTORY_LIMIT);
+
+        let mut signatures = Vec::new();
+        loop {
+            let page = client
+                .get_signatures_for_address_with_config(
+                    &address,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(SIGNATURES_PAGE_SIZE),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+                .await?;
+
+            for entry in &page {
+                let block_time = entry.block_time;
+                if matches!((block_time, query.end_time), (Some(t), Some(end)) if t > end) {
+                    continue;
+                }
+                // Signatures are returned newest first, so everything after this is older
+                if matches!((block_time, query.start_time), (Some(t), Some(start)) if t < start) {
+                    return Ok((signatures, None));
+                }
+                if signatures.len() == limit {
+                    let next_before = signatures.last().cloned();
+                    return Ok((signatures, next_before));
+                }
+                signatures.push(entry.signature.clone());
+            }
+
+            match page.last() {
+                Some(last) if page.len() == SIGNATURES_PAGE_SIZE => {
+                    before = Some(Signature::from_str(&last.signature)?);
+                }
+                _ => return Ok((signatures, None)),
+            }
+        }
+    }
+}
+
+fn address_transaction_cost(
+    address: &str,
+    analysis: &SolanaTransactionAnalysis,
+) -> AddressTransactionCost {
+    // The fee payer always comes first in the rent summary
+    let fee_payer = analysis
+        .rent_summary
+        .first()
+        .is_some_and(|summary| summary.payer == address);
+    let (fee, priority_fee) = if fee_payer {
+        (
+            analysis.transaction_fee.unwrap_or(0),
+            analysis.cost_summary.priority_fee,
+        )
+    } else {
+        (0, 0)
+    };
+
+    let tips = analysis
+        .tips
+        .iter()
+        .filter(|tip| tip.payer == address)
+        .map(|tip| tip.lamports)
+        .sum();
+    let (rent_deposited, rent_reclaimed) = rent_totals(
+        analysis
+            .rent_events
+            .iter()
+            .filter(|event| event.payer == address),
+    );
+
+    AddressTransactionCost {
+        signature: analysis.signature.clone(),
+        slot: analysis.slot,
+        block_time: analysis.block_time,
+        success: analysis.failure.is_none(),
+        fee_payer,
+        fee,
+        priority_fee,
+        tips,
+        rent_deposited,
+        rent_reclaimed,
+        total_cost: fee + tips,
+    }
+}
@@ -0,0 +1,704 @@
+use super::instructions::FlatInstruction;
+use super::SolanaChainManager;
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use solana_sdk::{bs58, hash::hashv};
+use std::{collections::HashMap, path::Path};
+
+/// Prefix of the self-CPI instruction Anchor's `emit_cpi!` uses to record events
+pub(super) const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+/// An Anchor IDL reduced to what is needed to decode instructions, events and errors.
+/// Both the legacy (< 0.30) and the current IDL formats are accepted.
+#[derive(Debug, Clone)]
+pub struct AnchorIdl {
+    pub name: String,
+    /// Program address declared in the IDL, if any
+    pub address: Option<String>,
+    instructions: Vec<IdlInstruction>,
+    events: Vec<IdlEvent>,
+    types: HashMap<String, Value>,
+    errors: Vec<(u32, String, Option<String>)>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlInstruction {
+    name: String,
+    discriminator: Vec<u8>,
+    accounts: Vec<String>,
+    args: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlEvent {
+    name: String,
+    discriminator: Vec<u8>,
+    /// Type body of the event, `None` when it is defined in `types`
+    body: Option<Value>,
+}
+
+impl AnchorIdl {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let idl: Value = serde_json::from_str(json)?;
+
+        let name = idl["metadata"]["name"]
+            .as_str()
+            .or_else(|| idl["name"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let address = idl["address"]
+            .as_str()
+            .or_else(|| idl["metadata"]["address"].as_str())
+            .map(str::to_string);
+
+        let instructions = idl["instructions"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("IDL has no instructions"))?
+            .iter()
+            .filter_map(|instruction| {
+                let name = instruction["name"].as_str()?.to_string();
+                let discriminator = discriminator(&instruction["discriminator"])
+                    .unwrap_or_else(|| sighash("global", &to_snake_case(&name)));
+                let mut accounts = Vec::new();
+                flatten_idl_accounts(&instruction["accounts"], "", &mut accounts);
+                let args = instruction["args"]
+                    .as_array()
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(|arg| {
+                                Some((arg["name"].as_str()?.to_string(), arg["type"].clone()))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                Some(IdlInstruction {
+                    name,
+                    discriminator,
+                    accounts,
+                    args,
+                })
+            })
+            .collect();
+
+        let events = idl["events"]
+            .as_array()
+            .map(|events| {
+                events
+                    .iter()
+                    .filter_map(|event| {
+                        let name = event["name"].as_str()?.to_string();
+                        Some(IdlEvent {
+                            discriminator: discriminator(&event["discriminator"])
+                                .unwrap_or_else(|| sighash("event", &name)),
+                            // Legacy IDLs list event fields inline
+                            body: event["fields"]
+                                .is_array()
+                                .then(|| json!({ "kind": "struct", "fields": event["fields"] })),
+                            name,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let types = idl["types"]
+            .as_array()
+            .map(|types| {
+                types
+                    .iter()
+                    .filter_map(|ty| Some((ty["name"].as_str()?.to_string(), ty["type"].clone())))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let errors = idl["errors"]
+            .as_array()
+            .map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(|error| {
+                        Some((
+                            error["code"].as_u64()? as u32,
+                            error["name"].as_str()?.to_string(),
+                            error["msg"].as_str().map(str::to_string),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AnchorIdl {
+            name,
+            address,
+            instructions,
+            events,
+            types,
+            errors,
+        })
+    }
+
+    /// Custom errors declared by the program as `(code, name, message)`
+    pub fn errors(&self) -> &[(u32, String, Option<String>)] {
+        &self.errors
+    }
+
+    fn decode_event(&self, data: &[u8]) -> Option<(String, Value)> {
+        let event = self
+            .events
+            .iter()
+            .find(|event| data.starts_with(&event.discriminator))?;
+        let body = match &event.body {
+            Some(body) => body.clone(),
+            None => self.types.get(&event.name)?.clone(),
+        };
+
+        let mut reader = BorshReader::new(&data[event.discriminator.len()..]);
+        let decoded = decode_type_body(&body, &mut reader, self)?;
+        Some((event.name.clone(), decoded))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedAccount {
+    /// Account name from the IDL, `None` for remaining accounts
+    pub name: Option<String>,
+    pub pubkey: String,
+}
+
+/// An instruction of a program with a loaded IDL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedInstruction {
+    pub program_id: String,
+    pub program_name: String,
+    pub instruction_index: usize,
+    pub inner_instruction_index: Option<usize>,
+    pub name: String,
+    pub accounts: Vec<DecodedAccount>,
+    /// Decoded arguments, `None` if the data did not match the IDL
+    pub args: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventSource {
+    /// `Program data:` log line
+    Log,
+    /// Self-CPI instruction emitted by `emit_cpi!`
+    SelfCpi,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedEvent {
+    pub program_id: String,
+    pub program_name: String,
+    pub instruction_index: Option<usize>,
+    pub name: String,
+    pub data: Value,
+    pub source: EventSource,
+}
+
+impl SolanaChainManager {
+    /// Load an Anchor IDL, using `program_id` or else the address declared in the IDL.
+    /// Returns the program ID the IDL was registered for.
+    pub fn load_idl(&mut self, program_id: Option<&str>, json: &str) -> Result<String> {
+        let idl = AnchorIdl::from_json(json)?;
+        let program_id = program_id
+            .map(str::to_string)
+            .or_else(|| idl.address.clone())
+            .ok_or_else(|| anyhow::anyhow!("IDL does not declare a program address"))?;
+
+        self.add_idl(program_id.clone(), idl);
+        Ok(program_id)
+    }
+
+    /// Load every `*.json` IDL in a directory, falling back to the file name as program ID.
+    /// Files that fail to load are skipped and reported by [`Self::idl_errors`]
+    pub(super) fn load_idl_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let idl = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| AnchorIdl::from_json(&json));
+            let idl = match idl {
+                Ok(idl) => idl,
+                Err(e) => {
+                    self.idl_errors
+                        .push(format!("Error loading IDL {}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            let file_stem = path.file_stem().and_then(|s| s.to_str()).map(str::to_string);
+            match idl.address.clone().or(file_stem) {
+                Some(program_id) => self.add_idl(program_id, idl),
+                None => self.idl_errors.push(format!(
+                    "Error loading IDL {}: no program address",
+                    path.display()
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Errors of the IDLs that could not be loaded from the configured IDL directory
+    pub fn idl_errors(&self) -> &[String] {
+        &self.idl_errors
+    }
+
+    fn add_idl(&mut self, program_id: String, idl: AnchorIdl) {
+        self.error_registry
+            .register(&program_id, idl.errors().iter().cloned());
+        self.idls.insert(program_id, idl);
+    }
+
+    pub(super) fn decode_instructions(
+        &self,
+        instructions: &[FlatInstruction],
+    ) -> Vec<DecodedInstruction> {
+        instructions
+            .iter()
+            .filter(|instruction| !instruction.data.starts_with(&EVENT_IX_TAG))
+            .filter_map(|instruction| {
+                let idl = self.idls.get(&instruction.program_id)?;
+                let idl_instruction = idl
+                    .instructions
+                    .iter()
+                    .find(|i| instruction.data.starts_with(&i.discriminator))?;
+
+                let accounts = instruction
+                    .accounts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pubkey)| DecodedAccount {
+                        name: idl_instruction.accounts.get(i).cloned(),
+                        pubkey: pubkey.clone(),
+                    })
+                    .collect();
+
+                let mut reader =
+                    BorshReader::new(&instruction.data[idl_instruction.discriminator.len()..]);
+                let args = idl_instruction
+                    .args
+                    .iter()
+                    .map(|(name, ty)| Some((name.clone(), decode_type(ty, &mut reader, idl)?)))
+                    .collect::<Option<Map<String, Value>>>()
+                    .map(Value::Object);
+
+                Some(DecodedInstruction {
+                    program_id: instruction.program_id.clone(),
+                    program_name: idl.name.clone(),
+                    instruction_index: instruction.outer_index,
+                    inner_instruction_index: instruction.inner_index,
+                    name: idl_instruction.name.clone(),
+                    accounts,
+                    args,
+                })
+            })
+            .collect()
+    }
+
+    pub(super) fn decode_events(
+        &self,
+        instructions: &[FlatInstruction],
+        logs: &[String],
+    ) -> Vec<DecodedEvent> {
+        raw_events(instructions, logs)
+            .into_iter()
+            .filter_map(|event| {
+                let idl = self.idls.get(&event.program_id)?;
+                let (name, data) = idl.decode_event(&event.data)?;
+                Some(DecodedEvent {
+                    program_name: idl.name.clone(),
+                    program_id: event.program_id,
+                    instruction_index: event.instruction_index,
+                    name,
+                    data,
+                    source: event.source,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Undecoded event data, starting with the event discriminator
+pub(super) struct RawEvent {
+    pub program_id: String,
+    pub instruction_index: Option<usize>,
+    pub data: Vec<u8>,
+    pub source: EventSource,
+}
+
+/// Events of all programs, from `Program data:` logs followed by self-CPI instructions
+pub(super) fn raw_events(instructions: &[FlatInstruction], logs: &[String]) -> Vec<RawEvent> {
+    let mut events = Vec::new();
+
+    // Events emitted through `emit!` are logged as base64 "Program data:" lines
+    let mut stack: Vec<&str> = Vec::new();
+    let mut instruction_index: Option<usize> = None;
+    for line in logs {
+        if let Some(data) = line.strip_prefix("Program data: ") {
+            let Some(program_id) = stack.last() else {
+                continue;
+            };
+            let Ok(data) = BASE64.decode(data) else {
+                continue;
+            };
+
+            events.push(RawEvent {
+                program_id: program_id.to_string(),
+                instruction_index,
+                data,
+                source: EventSource::Log,
+            });
+        } else if let Some((program_id, event)) = line
+            .strip_prefix("Program ")
+            .and_then(|rest| rest.split_once(' '))
+        {
+            if event.starts_with("invoke [") {
+                if event == "invoke [1]" {
+                    instruction_index = Some(instruction_index.map_or(0, |i| i + 1));
+                }
+                stack.push(program_id);
+            } else if event == "success" || event.starts_with("failed") {
+                stack.pop();
+            }
+        }
+    }
+
+    // Events emitted through `emit_cpi!` are self-CPIs carrying the event as data
+    for instruction in instructions {
+        let Some(data) = instruction.data.strip_prefix(&EVENT_IX_TAG) else {
+            continue;
+        };
+
+        events.push(RawEvent {
+            program_id: instruction.program_id.clone(),
+            instruction_index: Some(instruction.outer_index),
+            data: data.to_vec(),
+            source: EventSource::SelfCpi,
+        });
+    }
+
+    events
+}
+
+fn discriminator(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|byte| byte.as_u64().map(|b| b as u8))
+        .collect()
+}
+
+/// Anchor's 8-byte discriminator: the first bytes of `sha256("<namespace>:<name>")`
+pub(super) fn sighash(namespace: &str, name: &str) -> Vec<u8> {
+    hashv(&[namespace.as_bytes(), b":", name.as_bytes()]).to_bytes()[..8].to_vec()
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Flatten nested account groups into `group.account` names, in instruction order
+fn flatten_idl_accounts(accounts: &Value, prefix: &str, names: &mut Vec<String>) {
+    for account in accounts.as_array().into_iter().flatten() {
+        let Some(name) = account["name"].as_str() else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, name);
+        if account["accounts"].is_array() {
+            flatten_idl_accounts(&account["accounts"], &format!("{}.", name), names);
+        } else {
+            names.push(name);
+        }
+    }
+}
+
+struct BorshReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BorshReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BorshReader { data }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn read_len(&mut self) -> Option<usize> {
+        Some(u32::from_le_bytes(self.take_array()?) as usize)
+    }
+}
+
+fn decode_type(ty: &Value, reader: &mut BorshReader, idl: &AnchorIdl) -> Option<Value> {
+    if let Some(primitive) = ty.as_str() {
+        // 64-bit and wider integers are emitted as strings to survive JSON consumers
+        return Some(match primitive {
+            "bool" => json!(reader.take_array::<1>()?[0] != 0),
+            "u8" => json!(reader.take_array::<1>()?[0]),
+            "i8" => json!(reader.take_array::<1>()?[0] as i8),
+            "u16" => json!(u16::from_le_bytes(reader.take_array()?)),
+            "i16" => json!(i16::from_le_bytes(reader.take_array()?)),
+            "u32" => json!(u32::from_le_bytes(reader.take_array()?)),
+            "i32" => json!(i32::from_le_bytes(reader.take_array()?)),
+            "f32" => json!(f32::from_le_bytes(reader.take_array()?)),
+            "f64" => json!(f64::from_le_bytes(reader.take_array()?)),
+            "u64" => json!(u64::from_le_bytes(reader.take_array()?).to_string()),
+            "i64" => json!(i64::from_le_bytes(reader.take_array()?).to_string()),
+            "u128" => json!(u128::from_le_bytes(reader.take_array()?).to_string()),
+            "i128" => json!(i128::from_le_bytes(reader.take_array()?).to_string()),
+            "string" => {
+                let len = reader.read_len()?;
+                json!(String::from_utf8_lossy(reader.take(len)?))
+            }
+            "bytes" => {
+                let len = reader.read_len()?;
+                json!(format!("0x{}", hex::encode(reader.take(len)?)))
+            }
+            "publicKey" | "pubkey" => json!(bs58::encode(reader.take(32)?).into_string()),
+            _ => return None,
+        });
+    }
+
+    if let Some(inner) = ty.get("vec") {
+        let len = reader.read_len()?;
+        return (0..len)
+            .map(|_| decode_type(inner, reader, idl))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if let Some(inner) = ty.get("option") {
+        return match reader.take_array::<1>()?[0] {
+            0 => Some(Value::Null),
+            _ => decode_type(inner, reader, idl),
+        };
+    }
+    if let Some(inner) = ty.get("coption") {
+        return match u32::from_le_bytes(reader.take_array()?) {
+            0 => Some(Value::Null),
+            _ => decode_type(inner, reader, idl),
+        };
+    }
+    if let Some(array) = ty.get("array").and_then(Value::as_array) {
+        let (inner, len) = (array.first()?, array.get(1)?.as_u64()?);
+        return (0..len)
+            .map(|_| decode_type(inner, reader, idl))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if let Some(defined) = ty.get("defined") {
+        let name = defined.as_str().or_else(|| defined["name"].as_str())?;
+        let body = idl.types.get(name)?;
+        return decode_type_body(body, reader, idl);
+    }
+
+    None
+}
+
+/// Decode a type definition body: `{ "kind": "struct" | "enum" | "type", ... }`
+fn decode_type_body(body: &Value, reader: &mut BorshReader, idl: &AnchorIdl) -> Option<Value> {
+    match body["kind"].as_str()? {
+        "struct" => decode_fields(&body["fields"], reader, idl),
+        "enum" => {
+            let index = reader.take_array::<1>()?[0] as usize;
+            let variant = body["variants"].as_array()?.get(index)?;
+            let name = variant["name"].as_str()?.to_string();
+            match variant.get("fields") {
+                Some(fields) => {
+                    let mut object = Map::new();
+                    object.insert(name, decode_fields(fields, reader, idl)?);
+                    Some(Value::Object(object))
+                }
+                None => Some(Value::String(name)),
+            }
+        }
+        "type" => decode_type(&body["alias"], reader, idl),
+        _ => None,
+    }
+}
+
+/// Decode named fields into an object, or tuple fields into an array
+fn decode_fields(fields: &Value, reader: &mut BorshReader, idl: &AnchorIdl) -> Option<Value> {
+    let Some(fields) = fields.as_array() else {
+        return Some(Value::Object(Map::new()));
+    };
+
+    if fields.iter().all(|field| field.get("name").is_some()) {
+        let mut object = Map::new();
+        for field in fields {
+            object.insert(
+                field["name"].as_str()?.to_string(),
+                decode_type(&field["type"], reader, idl)?,
+            );
+        }
+        Some(Value::Object(object))
+    } else {
+        fields
+            .iter()
+            .map(|field| decode_type(field, reader, idl))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Types of an Anchor 0.30 IDL exercising primitives, containers and defined types
+    const IDL: &str = r#"{
+        "address": "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+        "metadata": { "name": "whirlpool" },
+        "instructions": [],
+        "types": [
+            {
+                "name": "Side",
+                "type": {
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Bid" },
+                        { "name": "Ask", "fields": [{ "name": "limit", "type": "u64" }] }
+                    ]
+                }
+            },
+            {
+                "name": "Order",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "owner", "type": "pubkey" },
+                        { "name": "amount", "type": "u64" },
+                        { "name": "side", "type": { "defined": { "name": "Side" } } },
+                        { "name": "memo", "type": { "option": "string" } },
+                        { "name": "ticks", "type": { "vec": "i32" } },
+                        { "name": "seed", "type": { "array": ["u8", 2] } },
+                        { "name": "active", "type": "bool" }
+                    ]
+                }
+            },
+            { "name": "Pair", "type": { "kind": "struct", "fields": ["u16", "i128"] } }
+        ]
+    }"#;
+
+    fn decode(ty: Value, data: &[u8]) -> Option<Value> {
+        let idl = AnchorIdl::from_json(IDL).unwrap();
+        decode_type(&ty, &mut BorshReader::new(data), &idl)
+    }
+
+    #[test]
+    fn decodes_primitives() {
+        assert_eq!(decode(json!("u8"), &[7]), Some(json!(7)));
+        assert_eq!(decode(json!("i16"), &[0xfe, 0xff]), Some(json!(-2)));
+        assert_eq!(
+            decode(json!("u64"), &u64::MAX.to_le_bytes()),
+            Some(json!("18446744073709551615"))
+        );
+        assert_eq!(
+            decode(json!("i128"), &(-5i128).to_le_bytes()),
+            Some(json!("-5"))
+        );
+        assert_eq!(
+            decode(json!("string"), &[3, 0, 0, 0, b'a', b'b', b'c']),
+            Some(json!("abc"))
+        );
+        assert_eq!(
+            decode(json!("bytes"), &[2, 0, 0, 0, 0xde, 0xad]),
+            Some(json!("0xdead"))
+        );
+        assert_eq!(
+            decode(json!("publicKey"), &[0; 32]),
+            Some(json!("11111111111111111111111111111111"))
+        );
+    }
+
+    #[test]
+    fn decodes_defined_struct() {
+        let mut data = vec![1; 32];
+        data.extend(1_500_000u64.to_le_bytes());
+        // Ask { limit: 42 }
+        data.push(1);
+        data.extend(42u64.to_le_bytes());
+        // Some("hi")
+        data.extend([1, 2, 0, 0, 0, b'h', b'i']);
+        // vec![-1, 3]
+        data.extend([2, 0, 0, 0]);
+        data.extend((-1i32).to_le_bytes());
+        data.extend(3i32.to_le_bytes());
+        data.extend([9, 8, 1]);
+
+        assert_eq!(
+            decode(json!({ "defined": { "name": "Order" } }), &data),
+            Some(json!({
+                "owner": "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi",
+                "amount": "1500000",
+                "side": { "Ask": { "limit": "42" } },
+                "memo": "hi",
+                "ticks": [-1, 3],
+                "seed": [9, 8],
+                "active": true
+            }))
+        );
+    }
+
+    #[test]
+    fn decodes_unit_variants_and_tuple_structs() {
+        assert_eq!(
+            decode(json!({ "defined": "Side" }), &[0]),
+            Some(json!("Bid"))
+        );
+
+        let mut data = 513u16.to_le_bytes().to_vec();
+        data.extend(7i128.to_le_bytes());
+        assert_eq!(
+            decode(json!({ "defined": "Pair" }), &data),
+            Some(json!([513, "7"]))
+        );
+    }
+
+    #[test]
+    fn decodes_empty_options() {
+        assert_eq!(decode(json!({ "option": "u32" }), &[0]), Some(Value::Null));
+        assert_eq!(
+            decode(json!({ "coption": "pubkey" }), &[0, 0, 0, 0]),
+            Some(Value::Null)
+        );
+    }
+
+    #[test]
+    fn rejects_short_and_unknown_input() {
+        assert_eq!(decode(json!("u64"), &[1, 2, 3]), None);
+        assert_eq!(decode(json!({ "vec": "u8" }), &[5, 0, 0, 0, 1]), None);
+        assert_eq!(decode(json!({ "defined": "Missing" }), &[0]), None);
+        assert_eq!(decode(json!({ "defined": "Side" }), &[2]), None);
+        assert_eq!(decode(json!("u256"), &[0; 32]), None);
+    }
+}
@@ -0,0 +1,323 @@
+use serde_json::Value;
+use solana_sdk::{
+    bs58,
+    instruction::CompiledInstruction,
+    message::{AccountKeys, VersionedMessage},
+    pubkey::Pubkey,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, parse_instruction,
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiCompiledInstruction,
+    UiInnerInstructions, UiInstruction, UiMessage, UiParsedInstruction,
+};
+use std::{collections::HashMap, str::FromStr};
+
+/// A top-level or inner instruction flattened into a single shape, regardless of
+/// whether the transaction was fetched as `jsonParsed` or raw `json`
+#[derive(Debug, Clone)]
+pub(crate) struct FlatInstruction {
+    /// Index of the top-level instruction this instruction belongs to
+    pub outer_index: usize,
+    /// Position inside `meta.inner_instructions`, `None` for top-level instructions
+    pub inner_index: Option<usize>,
+    pub program_id: String,
+    /// Account addresses in instruction order
+    pub accounts: Vec<String>,
+    /// `{ "type": ..., "info": ... }` as produced by the RPC instruction parsers
+    pub parsed: Option<Value>,
+    /// Raw instruction data, empty for instructions the RPC node already parsed
+    pub data: Vec<u8>,
+    /// CPI depth, 1 for top-level instructions. `None` for inner instructions of nodes that
+    /// do not report it
+    pub stack_height: Option<u32>,
+}
+
+impl FlatInstruction {
+    pub fn parsed_type(&self) -> Option<&str> {
+        self.parsed.as_ref()?.get("type")?.as_str()
+    }
+
+    pub fn info(&self) -> Option<&Value> {
+        self.parsed.as_ref()?.get("info")
+    }
+
+    pub fn info_str(&self, key: &str) -> Option<&str> {
+        self.info()?.get(key)?.as_str()
+    }
+
+    /// Read an integer field that the parsers emit either as a number or a string
+    pub fn info_u64(&self, key: &str) -> Option<u64> {
+        json_u64(self.info()?.get(key)?)
+    }
+}
+
+pub(crate) fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Account keys in the order used by `pre_balances`/`post_balances`, including
+/// addresses loaded from lookup tables
+pub(crate) fn full_account_keys(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<String> {
+    let mut keys = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui_transaction) => match &ui_transaction.message {
+            // Parsed messages already list lookup table addresses
+            UiMessage::Parsed(parsed_message) => {
+                return parsed_message
+                    .account_keys
+                    .iter()
+                    .map(|key| key.pubkey.clone())
+                    .collect();
+            }
+            UiMessage::Raw(raw_message) => raw_message.account_keys.clone(),
+        },
+        _ => return Vec::new(),
+    };
+
+    if let Some(meta) = &transaction.transaction.meta {
+        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+            keys.extend(loaded.writable.iter().cloned());
+            keys.extend(loaded.readonly.iter().cloned());
+        }
+    }
+
+    keys
+}
+
+/// Flatten top-level and inner instructions into execution order.
+///
+/// Compiled instructions of programs known to the RPC parsers (System, SPL Token,
+/// Associated Token Account, ...) are parsed locally so that callers see the same
+/// `parsed` value whichever encoding the transaction was fetched with.
+pub(crate) fn flatten_instructions(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<FlatInstruction> {
+    let top_level: Vec<UiInstruction> = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui_transaction) => match &ui_transaction.message {
+            UiMessage::Parsed(parsed_message) => parsed_message.instructions.clone(),
+            UiMessage::Raw(raw_message) => raw_message
+                .instructions
+                .iter()
+                .cloned()
+                .map(UiInstruction::Compiled)
+                .collect(),
+        },
+        _ => return Vec::new(),
+    };
+
+    let inner = match transaction
+        .transaction
+        .meta
+        .as_ref()
+        .map(|m| &m.inner_instructions)
+    {
+        Some(OptionSerializer::Some(inner)) => inner.clone(),
+        _ => Vec::new(),
+    };
+
+    flatten(&top_level, &inner, &full_account_keys(transaction))
+}
+
+/// Flatten the instructions of an unsent message, with inner instructions from a simulation
+pub(crate) fn flatten_message(
+    message: &VersionedMessage,
+    account_keys: &[String],
+    inner: &[UiInnerInstructions],
+) -> Vec<FlatInstruction> {
+    let top_level: Vec<UiInstruction> = message
+        .instructions()
+        .iter()
+        .map(|instruction| {
+            UiInstruction::Compiled(UiCompiledInstruction {
+                program_id_index: instruction.program_id_index,
+                accounts: instruction.accounts.clone(),
+                data: bs58::encode(&instruction.data).into_string(),
+                stack_height: None,
+            })
+        })
+        .collect();
+
+    flatten(&top_level, inner, account_keys)
+}
+
+fn flatten(
+    top_level: &[UiInstruction],
+    inner: &[UiInnerInstructions],
+    account_keys: &[String],
+) -> Vec<FlatInstruction> {
+    let pubkeys: Vec<Pubkey> = account_keys
+        .iter()
+        .map(|key| Pubkey::from_str(key).unwrap_or_default())
+        .collect();
+
+    let mut flattened = Vec::new();
+    for (outer_index, instruction) in top_level.iter().enumerate() {
+        flattened.push(flatten_one(
+            instruction,
+            outer_index,
+            None,
+            account_keys,
+            &pubkeys,
+        ));
+
+        for group in inner
+            .iter()
+            .filter(|group| group.index as usize == outer_index)
+        {
+            for (inner_index, instruction) in group.instructions.iter().enumerate() {
+                flattened.push(flatten_one(
+                    instruction,
+                    outer_index,
+                    Some(inner_index),
+                    account_keys,
+                    &pubkeys,
+                ));
+            }
+        }
+    }
+
+    flattened
+}
+
+fn flatten_one(
+    instruction: &UiInstruction,
+    outer_index: usize,
+    inner_index: Option<usize>,
+    account_keys: &[String],
+    pubkeys: &[Pubkey],
+) -> FlatInstruction {
+    match instruction {
+        UiInstruction::Compiled(UiCompiledInstruction {
+            program_id_index,
+            accounts,
+            data,
+            stack_height,
+        }) => {
+            let data = bs58::decode(data).into_vec().unwrap_or_default();
+            let compiled = CompiledInstruction {
+                program_id_index: *program_id_index,
+                accounts: accounts.clone(),
+                data: data.clone(),
+            };
+            // Out of range indexes make the parsers panic, so only parse well-formed instructions
+            let in_range = std::iter::once(program_id_index)
+                .chain(accounts.iter())
+                .all(|index| (*index as usize) < pubkeys.len());
+            let parsed = pubkeys
+                .get(*program_id_index as usize)
+                .filter(|_| in_range)
+                .and_then(|program_id| {
+                    parse_instruction::parse(
+                        program_id,
+                        &compiled,
+                        &AccountKeys::new(pubkeys, None),
+                        *stack_height,
+                    )
+                    .ok()
+                })
+                .map(|parsed| parsed.parsed);
+
+            FlatInstruction {
+                outer_index,
+                inner_index,
+                program_id: account_keys
+                    .get(*program_id_index as usize)
+                    .cloned()
+                    .unwrap_or_default(),
+                accounts: accounts
+                    .iter()
+                    .map(|index| {
+                        account_keys
+                            .get(*index as usize)
+                            .cloned()
+                            .unwrap_or_default()
+                    })
+                    .collect(),
+                parsed,
+                data,
+                stack_height: instruction_stack_height(inner_index, *stack_height),
+            }
+        }
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => FlatInstruction {
+            outer_index,
+            inner_index,
+            program_id: parsed.program_id.clone(),
+            accounts: Vec::new(),
+            parsed: Some(parsed.parsed.clone()),
+            data: Vec::new(),
+            stack_height: instruction_stack_height(inner_index, parsed.stack_height),
+        },
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(partial)) => FlatInstruction {
+            outer_index,
+            inner_index,
+            program_id: partial.program_id.clone(),
+            accounts: partial.accounts.clone(),
+            parsed: None,
+            data: bs58::decode(&partial.data).into_vec().unwrap_or_default(),
+            stack_height: instruction_stack_height(inner_index, partial.stack_height),
+        },
+    }
+}
+
+/// Top-level instructions are often reported without a stack height
+fn instruction_stack_height(inner_index: Option<usize>, reported: Option<u32>) -> Option<u32> {
+    match inner_index {
+        None => Some(1),
+        Some(_) => reported,
+    }
+}
+
+pub(crate) const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub(crate) const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub(crate) const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub(crate) const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub(crate) const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+pub(crate) const NATIVE_MINT: &str = "So11111111111111111111111111111111111111112";
+
+pub(crate) fn is_token_program(program_id: &str) -> bool {
+    program_id == TOKEN_PROGRAM_ID || program_id == TOKEN_2022_PROGRAM_ID
+}
+
+/// Pre and post lamport balances keyed by account address
+pub(crate) fn lamport_balances(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> HashMap<String, (u64, u64)> {
+    let mut balances = HashMap::new();
+
+    if let Some(meta) = &transaction.transaction.meta {
+        for (i, account) in full_account_keys(transaction).into_iter().enumerate() {
+            if let (Some(pre), Some(post)) = (meta.pre_balances.get(i), meta.post_balances.get(i)) {
+                balances.insert(account, (*pre, *post));
+            }
+        }
+    }
+
+    balances
+}
+
+/// Mint of every token account that appears in the pre or post token balances
+pub(crate) fn token_account_mints(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> HashMap<String, String> {
+    let mut mints = HashMap::new();
+    let account_keys = full_account_keys(transaction);
+
+    if let Some(meta) = &transaction.transaction.meta {
+        for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+            if let OptionSerializer::Some(balances) = balances {
+                for balance in balances {
+                    if let Some(account) = account_keys.get(balance.account_index as usize) {
+                        mints.insert(account.clone(), balance.mint.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    mints
+}
@@ -0,0 +1,313 @@
+use super::SolanaChainManager;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+
+/// A single program invocation reconstructed from the transaction logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramInvocation {
+    pub program_id: String,
+    /// Invocation depth as logged by the runtime, 1 for top-level instructions
+    pub depth: u32,
+    /// Compute units consumed including nested invocations, `None` for builtins
+    pub compute_units_consumed: Option<u64>,
+    /// Compute units consumed by this invocation alone
+    pub self_compute_units: Option<u64>,
+    /// Compute units available when the invocation finished (the "of M" part)
+    pub compute_units_limit: Option<u64>,
+    /// `None` when the logs end before the invocation completed
+    pub success: Option<bool>,
+    pub error: Option<String>,
+    pub inner_invocations: Vec<ProgramInvocation>,
+}
+
+/// Compute units consumed by one program across all of its invocations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramComputeUnits {
+    pub program_id: String,
+    pub invocations: u32,
+    pub compute_units_consumed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComputeUnitBreakdown {
+    /// Top-level invocations, each holding its CPIs
+    pub invocations: Vec<ProgramInvocation>,
+    /// Exclusive compute units per program, so the totals add up to the transaction
+    pub per_program: Vec<ProgramComputeUnits>,
+    /// Set when the runtime truncated the logs and the tree is incomplete
+    pub logs_truncated: bool,
+}
+
+impl SolanaChainManager {
+    pub(super) fn extract_compute_unit_breakdown(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Option<ComputeUnitBreakdown> {
+        match &transaction.transaction.meta.as_ref()?.log_messages {
+            OptionSerializer::Some(logs) => Some(parse_program_logs(logs)),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn parse_program_logs(logs: &[String]) -> ComputeUnitBreakdown {
+    let mut roots: Vec<ProgramInvocation> = Vec::new();
+    let mut stack: Vec<ProgramInvocation> = Vec::new();
+    let mut logs_truncated = false;
+
+    for line in logs {
+        if line == "Log truncated" {
+            logs_truncated = true;
+            break;
+        }
+
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        let Some((program_id, event)) = rest.split_once(' ') else {
+            continue;
+        };
+
+        if let Some(depth) = event
+            .strip_prefix("invoke [")
+            .and_then(|d| d.strip_suffix(']'))
+            .and_then(|d| d.parse().ok())
+        {
+            stack.push(ProgramInvocation {
+                program_id: program_id.to_string(),
+                depth,
+                compute_units_consumed: None,
+                self_compute_units: None,
+                compute_units_limit: None,
+                success: None,
+                error: None,
+                inner_invocations: Vec::new(),
+            });
+        } else if let Some(consumed) = event.strip_prefix("consumed ") {
+            // "consumed N of M compute units"
+            let mut parts = consumed.split_whitespace();
+            if let Some(current) = stack.last_mut().filter(|c| c.program_id == program_id) {
+                current.compute_units_consumed = parts.next().and_then(|n| n.parse().ok());
+                current.compute_units_limit = parts.nth(1).and_then(|m| m.parse().ok());
+            }
+        } else if event == "success" || event.starts_with("failed") {
+            if stack.last().map(|c| c.program_id.as_str()) != Some(program_id) {
+                continue;
+            }
+            let mut invocation = stack.pop().unwrap();
+            invocation.success = Some(event == "success");
+            invocation.error = event.strip_prefix("failed: ").map(str::to_string);
+            finish_invocation(invocation, &mut stack, &mut roots);
+        }
+    }
+
+    // Anything still open never logged a result
+    if !stack.is_empty() {
+        logs_truncated = true;
+    }
+    while let Some(invocation) = stack.pop() {
+        finish_invocation(invocation, &mut stack, &mut roots);
+    }
+
+    let mut per_program: Vec<ProgramComputeUnits> = Vec::new();
+    accumulate_per_program(&roots, &mut per_program);
+    per_program.sort_by_key(|p| std::cmp::Reverse(p.compute_units_consumed));
+
+    ComputeUnitBreakdown {
+        invocations: roots,
+        per_program,
+        logs_truncated,
+    }
+}
+
+fn finish_invocation(
+    mut invocation: ProgramInvocation,
+    stack: &mut [ProgramInvocation],
+    roots: &mut Vec<ProgramInvocation>,
+) {
+    invocation.self_compute_units = invocation.compute_units_consumed.map(|total| {
+        let nested: u64 = invocation
+            .inner_invocations
+            .iter()
+            .filter_map(|inner| inner.compute_units_consumed)
+            .sum();
+        total.saturating_sub(nested)
+    });
+
+    match stack.last_mut() {
+        Some(parent) => parent.inner_invocations.push(invocation),
+        None => roots.push(invocation),
+    }
+}
+
+fn accumulate_per_program(
+    invocations: &[ProgramInvocation],
+    totals: &mut Vec<ProgramComputeUnits>,
+) {
+    for invocation in invocations {
+        let entry = match totals
+            .iter()
+            .position(|t| t.program_id == invocation.program_id)
+        {
+            Some(index) => &mut totals[index],
+            None => {
+                totals.push(ProgramComputeUnits {
+                    program_id: invocation.program_id.clone(),
+                    invocations: 0,
+                    compute_units_consumed: 0,
+                });
+                totals.last_mut().unwrap()
+            }
+        };
+        entry.invocations += 1;
+        entry.compute_units_consumed += invocation.self_compute_units.unwrap_or(0);
+
+        accumulate_per_program(&invocation.inner_invocations, totals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPUTE_BUDGET: &str = "ComputeBudget111111111111111111111111111111";
+    const JUPITER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+    const WHIRLPOOL: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// A Jupiter route through a Whirlpool, whose swap moves tokens with two CPIs
+    fn route_logs() -> Vec<String> {
+        logs(&[
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+            "Program log: Instruction: Route",
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc invoke [2]",
+            "Program log: Instruction: Swap",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [3]",
+            "Program log: Instruction: Transfer",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 171622 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [3]",
+            "Program log: Instruction: Transfer",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4736 of 163985 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc consumed 41248 of 196400 compute units",
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc success",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 consumed 58931 of 199850 compute units",
+            "Program return: JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 QEIPAAAAAAA=",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 success",
+        ])
+    }
+
+    #[test]
+    fn nests_invocations_by_depth() {
+        let breakdown = parse_program_logs(&route_logs());
+
+        assert!(!breakdown.logs_truncated);
+        assert_eq!(breakdown.invocations.len(), 2);
+        let compute_budget = &breakdown.invocations[0];
+        assert_eq!(compute_budget.program_id, COMPUTE_BUDGET);
+        assert_eq!(compute_budget.compute_units_consumed, None);
+        assert_eq!(compute_budget.success, Some(true));
+
+        let route = &breakdown.invocations[1];
+        assert_eq!(route.program_id, JUPITER);
+        assert_eq!(route.depth, 1);
+        assert_eq!(route.compute_units_consumed, Some(58931));
+        assert_eq!(route.compute_units_limit, Some(199850));
+        assert_eq!(route.self_compute_units, Some(58931 - 41248));
+
+        let swap = &route.inner_invocations[0];
+        assert_eq!(swap.program_id, WHIRLPOOL);
+        assert_eq!(swap.depth, 2);
+        assert_eq!(swap.self_compute_units, Some(41248 - 4645 - 4736));
+        assert_eq!(swap.inner_invocations.len(), 2);
+        assert!(swap
+            .inner_invocations
+            .iter()
+            .all(|transfer| transfer.program_id == TOKEN && transfer.depth == 3));
+    }
+
+    #[test]
+    fn per_program_totals_add_up() {
+        let breakdown = parse_program_logs(&route_logs());
+
+        let totals: Vec<(&str, u32, u64)> = breakdown
+            .per_program
+            .iter()
+            .map(|p| {
+                (
+                    p.program_id.as_str(),
+                    p.invocations,
+                    p.compute_units_consumed,
+                )
+            })
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                (WHIRLPOOL, 1, 31867),
+                (JUPITER, 1, 17683),
+                (TOKEN, 2, 9381),
+                (COMPUTE_BUDGET, 1, 0),
+            ]
+        );
+        assert_eq!(totals.iter().map(|(_, _, units)| units).sum::<u64>(), 58931);
+    }
+
+    #[test]
+    fn records_failures() {
+        let breakdown = parse_program_logs(&logs(&[
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc invoke [1]",
+            "Program log: AnchorError occurred. Error Code: AmountOutBelowMinimum.",
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc consumed 21870 of 200000 compute units",
+            "Program whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc failed: custom program error: 0x1794",
+        ]));
+
+        let swap = &breakdown.invocations[0];
+        assert_eq!(swap.success, Some(false));
+        assert_eq!(swap.error.as_deref(), Some("custom program error: 0x1794"));
+        assert_eq!(swap.compute_units_consumed, Some(21870));
+    }
+
+    #[test]
+    fn closes_invocations_cut_off_by_truncation() {
+        let mut lines = route_logs();
+        lines.truncate(8);
+        lines.push("Log truncated".to_string());
+        lines.push("Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success".to_string());
+
+        let breakdown = parse_program_logs(&lines);
+
+        assert!(breakdown.logs_truncated);
+        assert_eq!(breakdown.invocations.len(), 2);
+        let route = &breakdown.invocations[1];
+        assert_eq!(route.success, None);
+        assert_eq!(route.compute_units_consumed, None);
+        let swap = &route.inner_invocations[0];
+        assert_eq!(swap.success, None);
+        let transfer = &swap.inner_invocations[0];
+        assert_eq!(transfer.program_id, TOKEN);
+        assert_eq!(transfer.success, None);
+    }
+
+    #[test]
+    fn flags_logs_ending_mid_invocation() {
+        let mut lines = route_logs();
+        lines.truncate(10);
+
+        let breakdown = parse_program_logs(&lines);
+
+        assert!(breakdown.logs_truncated);
+        let swap = &breakdown.invocations[1].inner_invocations[0];
+        assert_eq!(swap.inner_invocations[0].success, Some(true));
+        assert_eq!(swap.success, None);
+    }
+}
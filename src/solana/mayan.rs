@@ -0,0 +1,84 @@
+use super::idl::sighash;
+use super::instructions::FlatInstruction;
+use super::SolanaChainManager;
+use crate::cctp::{CctpTransfer, CctpTransferKind};
+use crate::mayan::mctp_order_id;
+use serde::{Deserialize, Serialize};
+
+const SWIFT_PROGRAM_ID: &str = "BLZRi6frs4X4DNLw56V4EXai1b6QVESN1BhHBTYM9VcY";
+const MCTP_PROGRAM_ID: &str = "dkpZqrxHFrhziEMQ931GLtfy11nFkCsfMftH9u6QwBU";
+
+/// Swift instructions opening an order, with the position of the order state PDA in their
+/// accounts
+const SWIFT_ORDER_INSTRUCTIONS: &[(&str, usize)] = &[("init_order", 2)];
+
+/// A Mayan Swift or MCTP order opened by the transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaMayanOrder {
+    /// `SWIFT` or `MCTP`
+    pub protocol: String,
+    /// `<source domain>:<nonce>` of the CCTP burn of an MCTP order. Swift orders are
+    /// identified by their order hash, which is only known to the Mayan Explorer
+    pub order_id: Option<String>,
+    /// Order state PDA of a Swift order
+    pub order_state: Option<String>,
+    pub instruction_index: usize,
+}
+
+impl SolanaChainManager {
+    /// Swift orders from their `init_order` instruction and MCTP orders from the CCTP burns
+    /// of the MCTP program
+    pub(super) fn extract_mayan_orders(
+        &self,
+        instructions: &[FlatInstruction],
+        cctp_transfers: &[CctpTransfer],
+    ) -> Vec<SolanaMayanOrder> {
+        let mut orders: Vec<SolanaMayanOrder> = Vec::new();
+        // MCTP burns one message per order, in instruction order
+        let mut burns = cctp_transfers
+            .iter()
+            .filter(|transfer| transfer.kind == CctpTransferKind::Burn)
+            .filter_map(mctp_order_id);
+
+        for instruction in instructions {
+            match instruction.program_id.as_str() {
+                SWIFT_PROGRAM_ID => {
+                    let Some((_, account_index)) = SWIFT_ORDER_INSTRUCTIONS
+                        .iter()
+                        .find(|(name, _)| instruction.data.starts_with(&sighash("global", name)))
+                    else {
+                        continue;
+                    };
+                    let Some(state) = instruction.accounts.get(*account_index) else {
+                        continue;
+                    };
+                    orders.push(SolanaMayanOrder {
+                        protocol: "SWIFT".to_string(),
+                        order_id: None,
+                        order_state: Some(state.clone()),
+                        instruction_index: instruction.outer_index,
+                    });
+                }
+                // One order per invoking instruction, whose CPIs into CCTP burn on its behalf
+                MCTP_PROGRAM_ID
+                    if !orders.iter().any(|order| {
+                        order.protocol == "MCTP"
+                            && order.instruction_index == instruction.outer_index
+                    }) =>
+                {
+                    let Some(order_id) = burns.next() else {
+                        continue;
+                    };
+                    orders.push(SolanaMayanOrder {
+                        protocol: "MCTP".to_string(),
+                        order_id: Some(order_id),
+                        order_state: None,
+                        instruction_index: instruction.outer_index,
+                    });
+                }
+                _ => {}
+            }
+        }
+        orders
+    }
+}
@@ -0,0 +1,88 @@
+use super::SolanaChainManager;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Compute unit price statistics over recent slots, in micro-lamports per compute unit.
+/// Comparable with `SolanaCostSummary::compute_unit_price` of analyzed transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityFeeEstimate {
+    pub network: String,
+    /// Writable accounts the fees were filtered by, empty for the global fee
+    pub accounts: Vec<String>,
+    pub slot_count: usize,
+    pub oldest_slot: Option<u64>,
+    pub newest_slot: Option<u64>,
+    /// Slots whose minimum landed fee was above zero
+    pub nonzero_slot_count: usize,
+    pub min: u64,
+    pub p25: u64,
+    pub p50: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub max: u64,
+    pub mean: u64,
+}
+
+impl SolanaChainManager {
+    /// Estimate the compute unit price from `getRecentPrioritizationFees` for transactions
+    /// that write to the given accounts
+    pub async fn estimate_priority_fee(
+        &self,
+        network: &str,
+        accounts: &[String],
+    ) -> Result<PriorityFeeEstimate> {
+        let client = self
+            .clients
+            .get(network)
+            .ok_or_else(|| anyhow::anyhow!("Network not supported: {}", network))?;
+
+        // The RPC node accepts at most 128 accounts
+        if accounts.len() > 128 {
+            return Err(anyhow::anyhow!("Too many accounts: {}", accounts.len()));
+        }
+        let pubkeys = accounts
+            .iter()
+            .map(|account| Pubkey::from_str(account))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid account: {}", e))?;
+
+        let fees = client.get_recent_prioritization_fees(&pubkeys).await?;
+
+        let mut prices: Vec<u64> = fees.iter().map(|fee| fee.prioritization_fee).collect();
+        prices.sort_unstable();
+        let mean = if prices.is_empty() {
+            0
+        } else {
+            (prices.iter().map(|p| *p as u128).sum::<u128>() / prices.len() as u128) as u64
+        };
+
+        Ok(PriorityFeeEstimate {
+            network: network.to_string(),
+            accounts: accounts.to_vec(),
+            slot_count: prices.len(),
+            oldest_slot: fees.iter().map(|fee| fee.slot).min(),
+            newest_slot: fees.iter().map(|fee| fee.slot).max(),
+            nonzero_slot_count: prices.iter().filter(|p| **p > 0).count(),
+            min: prices.first().copied().unwrap_or(0),
+            p25: percentile(&prices, 25),
+            p50: percentile(&prices, 50),
+            p75: percentile(&prices, 75),
+            p90: percentile(&prices, 90),
+            p95: percentile(&prices, 95),
+            max: prices.last().copied().unwrap_or(0),
+            mean,
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], percentile: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percentile * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
@@ -0,0 +1,54 @@
+use super::{SolanaChainManager, SolanaTransactionAnalysis};
+use anyhow::Result;
+use serde_json::Value;
+use solana_transaction_status::{
+    EncodableWithMeta, EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+};
+
+/// Parse a `getTransaction` dump, either the full JSON-RPC response or its `result`.
+///
+/// Binary (`base58`/`base64`) encoded transactions are converted to the `json` encoding
+/// so they can be analyzed like transactions fetched from a node.
+pub fn parse_raw_transaction(json: &str) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let mut value: Value = serde_json::from_str(json)?;
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+    if value.is_null() {
+        return Err(anyhow::anyhow!("Transaction not found in JSON"));
+    }
+
+    let mut transaction: EncodedConfirmedTransactionWithStatusMeta = serde_json::from_value(value)?;
+    if let Some(decoded) = transaction.transaction.transaction.decode() {
+        transaction.transaction.transaction = decoded.json_encode();
+    }
+
+    Ok(transaction)
+}
+
+impl SolanaChainManager {
+    /// Analyze a transaction JSON dump offline, without Token-2022 mint data
+    pub async fn analyze_raw_transaction(
+        &self,
+        network: &str,
+        json: &str,
+    ) -> Result<SolanaTransactionAnalysis> {
+        let transaction = parse_raw_transaction(json)?;
+
+        let signature = match &transaction.transaction.transaction {
+            EncodedTransaction::Json(ui_transaction) => ui_transaction.signatures.first().cloned(),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow::anyhow!("Transaction has no signature"))?;
+
+        // Accept both network keys ("mainnet") and cluster names ("mainnet-beta")
+        let network = self
+            .chains
+            .get(network)
+            .map(|chain| chain.network.clone())
+            .unwrap_or_else(|| network.to_string());
+
+        self.analyze_transaction_details(&signature, &network, transaction, Vec::new())
+            .await
+    }
+}
@@ -0,0 +1,250 @@
+use super::instructions::{
+    full_account_keys, is_token_program, lamport_balances, FlatInstruction,
+    ASSOCIATED_TOKEN_PROGRAM_ID, NATIVE_MINT, SYSTEM_PROGRAM_ID,
+};
+use super::SolanaChainManager;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+use std::collections::HashMap;
+
+/// Bytes of account metadata charged on top of the data length
+const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
+/// Lamports per byte for two years of rent, the rent-exempt threshold
+const RENT_EXEMPT_LAMPORTS_PER_BYTE: u64 = 6960;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RentEventKind {
+    /// System `createAccount` / `createAccountWithSeed`
+    CreateAccount,
+    /// Associated Token Account `create` / `createIdempotent` that created a new account
+    CreateAssociatedTokenAccount,
+    /// SPL Token / Token-2022 `closeAccount`
+    CloseAccount,
+}
+
+/// Rent deposited into a new account or reclaimed from a closed one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RentEvent {
+    pub kind: RentEventKind,
+    pub account: String,
+    /// Wallet that funded the account, or that received its lamports on close
+    pub payer: String,
+    pub lamports: u64,
+    pub instruction_index: usize,
+}
+
+/// Rent and cost totals for a single wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RentPayerSummary {
+    pub payer: String,
+    pub rent_deposited: u64,
+    pub rent_reclaimed: u64,
+    /// Rent still locked in accounts after the transaction (refundable)
+    pub net_rent: i64,
+    /// Transaction fee, only non-zero for the fee payer
+    pub fee_paid: u64,
+    pub sol_balance_change: i64,
+    /// SOL that left the wallet and is not parked in refundable rent
+    pub non_refundable_cost: i64,
+}
+
+impl SolanaChainManager {
+    pub(super) fn extract_rent_events(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        instructions: &[FlatInstruction],
+    ) -> Vec<RentEvent> {
+        let balances = lamport_balances(transaction);
+        let native_pre_amounts = self.native_mint_pre_amounts(transaction);
+
+        let mut events: Vec<RentEvent> = Vec::new();
+        // Lamports deposited into accounts created in this transaction
+        let mut deposits: HashMap<String, u64> = HashMap::new();
+        // ATA creations waiting for the System CPI that funds them
+        let mut pending_ata: HashMap<String, usize> = HashMap::new();
+
+        for instruction in instructions {
+            match (instruction.program_id.as_str(), instruction.parsed_type()) {
+                (SYSTEM_PROGRAM_ID, Some("createAccount" | "createAccountWithSeed")) => {
+                    let (Some(account), Some(payer), Some(lamports)) = (
+                        instruction.info_str("newAccount"),
+                        instruction.info_str("source"),
+                        instruction.info_u64("lamports"),
+                    ) else {
+                        continue;
+                    };
+                    // Lamports above the rent-exempt minimum are funds, e.g. wrapped SOL
+                    let lamports = match instruction.info_u64("space") {
+                        Some(space) => lamports.min(rent_exempt_minimum(space)),
+                        None => lamports,
+                    };
+
+                    deposits.insert(account.to_string(), lamports);
+                    if let Some(index) = pending_ata.remove(account) {
+                        events[index].lamports = lamports;
+                    } else {
+                        events.push(RentEvent {
+                            kind: RentEventKind::CreateAccount,
+                            account: account.to_string(),
+                            payer: payer.to_string(),
+                            lamports,
+                            instruction_index: instruction.outer_index,
+                        });
+                    }
+                }
+                (ASSOCIATED_TOKEN_PROGRAM_ID, Some("create" | "createIdempotent")) => {
+                    let (Some(account), Some(payer)) = (
+                        instruction.info_str("account"),
+                        instruction.info_str("source"),
+                    ) else {
+                        continue;
+                    };
+
+                    // `createIdempotent` on an existing account does not create anything
+                    let pre_balance = balances.get(account).map(|(pre, _)| *pre).unwrap_or(0);
+                    if pre_balance != 0 {
+                        continue;
+                    }
+
+                    pending_ata.insert(account.to_string(), events.len());
+                    events.push(RentEvent {
+                        kind: RentEventKind::CreateAssociatedTokenAccount,
+                        account: account.to_string(),
+                        payer: payer.to_string(),
+                        lamports: 0,
+                        instruction_index: instruction.outer_index,
+                    });
+                }
+                (program_id, Some("closeAccount")) if is_token_program(program_id) => {
+                    let (Some(account), Some(destination)) = (
+                        instruction.info_str("account"),
+                        instruction.info_str("destination"),
+                    ) else {
+                        continue;
+                    };
+
+                    // Only the rent reserve counts as reclaimed rent; wrapped SOL returned
+                    // by closing a native account is a token movement
+                    let lamports = match deposits.get(account) {
+                        Some(deposited) => *deposited,
+                        None => {
+                            let pre_balance =
+                                balances.get(account).map(|(pre, _)| *pre).unwrap_or(0);
+                            pre_balance.saturating_sub(
+                                native_pre_amounts.get(account).copied().unwrap_or(0),
+                            )
+                        }
+                    };
+
+                    events.push(RentEvent {
+                        kind: RentEventKind::CloseAccount,
+                        account: account.to_string(),
+                        payer: destination.to_string(),
+                        lamports,
+                        instruction_index: instruction.outer_index,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // Without inner instructions the funding CPI is invisible, fall back to the final balance
+        for (account, index) in pending_ata {
+            if let Some((_, post)) = balances.get(&account) {
+                events[index].lamports = *post;
+            }
+        }
+
+        events
+    }
+
+    pub(super) fn summarize_rent(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        events: &[RentEvent],
+    ) -> Vec<RentPayerSummary> {
+        let balances = lamport_balances(transaction);
+        let fee = transaction
+            .transaction
+            .meta
+            .as_ref()
+            .map(|m| m.fee)
+            .unwrap_or(0);
+        let fee_payer = full_account_keys(transaction).into_iter().next();
+
+        let mut payers: Vec<String> = fee_payer.iter().cloned().collect();
+        for event in events {
+            if !payers.contains(&event.payer) {
+                payers.push(event.payer.clone());
+            }
+        }
+
+        payers
+            .into_iter()
+            .map(|payer| {
+                let (rent_deposited, rent_reclaimed) =
+                    rent_totals(events.iter().filter(|event| event.payer == payer));
+                let net_rent = rent_deposited as i64 - rent_reclaimed as i64;
+                let sol_balance_change = balances
+                    .get(&payer)
+                    .map(|(pre, post)| *post as i64 - *pre as i64)
+                    .unwrap_or(0);
+
+                RentPayerSummary {
+                    fee_paid: if fee_payer.as_ref() == Some(&payer) {
+                        fee
+                    } else {
+                        0
+                    },
+                    payer,
+                    rent_deposited,
+                    rent_reclaimed,
+                    net_rent,
+                    sol_balance_change,
+                    non_refundable_cost: -sol_balance_change - net_rent,
+                }
+            })
+            .collect()
+    }
+
+    /// Wrapped SOL amounts held by native mint token accounts before the transaction
+    fn native_mint_pre_amounts(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> HashMap<String, u64> {
+        let mut amounts = HashMap::new();
+        let account_keys = full_account_keys(transaction);
+
+        if let Some(meta) = &transaction.transaction.meta {
+            if let OptionSerializer::Some(pre_token_balances) = &meta.pre_token_balances {
+                for balance in pre_token_balances.iter().filter(|b| b.mint == NATIVE_MINT) {
+                    if let (Some(account), Ok(amount)) = (
+                        account_keys.get(balance.account_index as usize),
+                        balance.ui_token_amount.amount.parse::<u64>(),
+                    ) {
+                        amounts.insert(account.clone(), amount);
+                    }
+                }
+            }
+        }
+
+        amounts
+    }
+}
+
+/// Total rent deposited and reclaimed by a set of events
+pub(crate) fn rent_totals<'a>(events: impl IntoIterator<Item = &'a RentEvent>) -> (u64, u64) {
+    events
+        .into_iter()
+        .fold((0, 0), |(deposited, reclaimed), event| match event.kind {
+            RentEventKind::CloseAccount => (deposited, reclaimed + event.lamports),
+            _ => (deposited + event.lamports, reclaimed),
+        })
+}
+
+/// Lamports an account of `space` bytes must hold to be exempt from rent
+pub(super) fn rent_exempt_minimum(space: u64) -> u64 {
+    (space + ACCOUNT_STORAGE_OVERHEAD) * RENT_EXEMPT_LAMPORTS_PER_BYTE
+}
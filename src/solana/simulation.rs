@@ -0,0 +1,254 @@
+use super::instructions::{flatten_message, is_token_program};
+use super::{
+    SolBalanceChange, SolanaChainManager, SolanaCostSummary, SolanaTip, SolanaTransactionFailure,
+    SolanaTransfer, TokenBalanceChange,
+};
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{
+    parse_address_lookup_table::{parse_address_lookup_table, LookupTableAccountType},
+    parse_token::{parse_token_v3, TokenAccountType},
+    UiAccountEncoding,
+};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+};
+use solana_sdk::{
+    bs58, commitment_config::CommitmentConfig, hash::Hash, message::VersionedMessage,
+    pubkey::Pubkey, transaction::VersionedTransaction,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use std::{collections::HashMap, str::FromStr};
+
+/// Accounts fetched per `getMultipleAccounts` request
+const MULTIPLE_ACCOUNTS_CHUNK_SIZE: usize = 100;
+
+/// Expected cost and effects of a transaction that has not been sent yet
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SolanaSimulation {
+    pub network: String,
+    /// Slot the simulation ran against
+    pub slot: u64,
+    pub transaction_status: String,
+    pub failure: Option<SolanaTransactionFailure>,
+    /// Fee from `getFeeForMessage`
+    pub fee: u64,
+    pub compute_units_consumed: Option<u64>,
+    /// Fee, priority fee and tips; rent shows up in `sol_balance_changes`
+    pub cost_summary: SolanaCostSummary,
+    pub logs: Vec<String>,
+    /// Projected changes, empty when the simulation failed
+    pub sol_balance_changes: Vec<SolBalanceChange>,
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+    pub transfers: Vec<SolanaTransfer>,
+    pub tips: Vec<SolanaTip>,
+}
+
+impl SolanaChainManager {
+    /// Simulate a base64 or base58 serialized legacy or v0 transaction and project its costs
+    pub async fn simulate_transaction(
+        &self,
+        network: &str,
+        transaction: &str,
+    ) -> Result<SolanaSimulation> {
+        let client = self
+            .clients
+            .get(network)
+            .ok_or_else(|| anyhow::anyhow!("Network not supported: {}", network))?;
+
+        let transaction = decode_transaction(transaction)?;
+        let account_keys = resolve_account_keys(client, &transaction.message).await?;
+        let pubkeys = account_keys
+            .iter()
+            .map(|key| Pubkey::from_str(key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Account state before the transaction, to diff against the simulated state
+        let mut pre_accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MULTIPLE_ACCOUNTS_CHUNK_SIZE) {
+            pre_accounts.extend(client.get_multiple_accounts(chunk).await?);
+        }
+
+        // Simulate against a fresh blockhash, returning the post state of every account
+        let response = client
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: account_keys.clone(),
+                    }),
+                    min_context_slot: None,
+                    inner_instructions: true,
+                },
+            )
+            .await?;
+        let result = response.value;
+
+        // The fee depends on the message only, but the blockhash must still be valid
+        let blockhash = match &result.replacement_blockhash {
+            Some(replacement) => Hash::from_str(&replacement.blockhash)?,
+            None => client.get_latest_blockhash().await?,
+        };
+        let mut message = transaction.message.clone();
+        message.set_recent_blockhash(blockhash);
+        let fee = match &message {
+            VersionedMessage::Legacy(message) => client.get_fee_for_message(message).await?,
+            VersionedMessage::V0(message) => client.get_fee_for_message(message).await?,
+        };
+
+        // Project balance changes from the pre and simulated post account state
+        let mut sol_balance_changes = Vec::new();
+        let mut token_balance_changes = Vec::new();
+        let mut mints = HashMap::new();
+        if let Some(post_accounts) = &result.accounts {
+            for ((address, pre), post) in account_keys.iter().zip(&pre_accounts).zip(post_accounts)
+            {
+                let pre_balance = pre.as_ref().map(|a| a.lamports).unwrap_or(0);
+                let post_balance = post.as_ref().map(|a| a.lamports).unwrap_or(0);
+                if pre_balance != post_balance {
+                    sol_balance_changes.push(SolBalanceChange {
+                        address: address.clone(),
+                        pre_balance,
+                        post_balance,
+                        balance_change: post_balance as i64 - pre_balance as i64,
+                    });
+                }
+
+                let pre_token = pre
+                    .as_ref()
+                    .filter(|a| is_token_program(&a.owner.to_string()))
+                    .and_then(|a| token_account(&a.data));
+                let post_token = post
+                    .as_ref()
+                    .filter(|a| is_token_program(&a.owner))
+                    .and_then(|a| token_account(&a.data.decode()?));
+                let Some((mint, _)) = post_token.as_ref().or(pre_token.as_ref()) else {
+                    continue;
+                };
+                mints.insert(address.clone(), mint.clone());
+
+                let pre_amount = pre_token.as_ref().map(|(_, amount)| *amount);
+                let post_amount = post_token.as_ref().map(|(_, amount)| *amount);
+                if pre_amount != post_amount {
+                    token_balance_changes.push(TokenBalanceChange {
+                        address: address.clone(),
+                        mint: mint.clone(),
+                        token_account: address.clone(),
+                        pre_balance: pre_amount,
+                        post_balance: post_amount,
+                        balance_change: Some(
+                            post_amount.unwrap_or(0) as i64 - pre_amount.unwrap_or(0) as i64,
+                        ),
+                    });
+                }
+            }
+        }
+
+        let logs = result.logs.clone().unwrap_or_default();
+        let instructions = flatten_message(
+            &transaction.message,
+            &account_keys,
+            result.inner_instructions.as_deref().unwrap_or_default(),
+        );
+        let failure = result
+            .err
+            .as_ref()
+            .map(|err| self.decode_failure(err, &instructions, &logs));
+
+        let transfers = self.decode_transfers(&instructions, &mints);
+        let tips = self.extract_tips(&transfers);
+        let cost_summary = self.summarize_costs(fee, &instructions, &tips, &[]);
+
+        Ok(SolanaSimulation {
+            network: network.to_string(),
+            slot: response.context.slot,
+            transaction_status: if failure.is_none() {
+                "Success".to_string()
+            } else {
+                "Failed".to_string()
+            },
+            failure,
+            fee,
+            compute_units_consumed: result.units_consumed,
+            cost_summary,
+            logs,
+            sol_balance_changes,
+            token_balance_changes,
+            transfers,
+            tips,
+        })
+    }
+}
+
+/// Decode a wire-format transaction, accepting base64 or base58
+fn decode_transaction(encoded: &str) -> Result<VersionedTransaction> {
+    let encoded = encoded.trim();
+    [
+        BASE64.decode(encoded).ok(),
+        bs58::decode(encoded).into_vec().ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|bytes| bincode::deserialize::<VersionedTransaction>(&bytes).ok())
+    .ok_or_else(|| anyhow::anyhow!("Invalid transaction: expected base64 or base58 wire format"))
+}
+
+/// Static account keys followed by the writable and readonly addresses of lookup tables
+async fn resolve_account_keys(
+    client: &RpcClient,
+    message: &VersionedMessage,
+) -> Result<Vec<String>> {
+    let mut keys: Vec<String> = message
+        .static_account_keys()
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+
+    let Some(lookups) = message.address_table_lookups() else {
+        return Ok(keys);
+    };
+
+    let mut writable = Vec::new();
+    let mut readonly = Vec::new();
+    for lookup in lookups {
+        let account = client.get_account(&lookup.account_key).await?;
+        let LookupTableAccountType::LookupTable(table) = parse_address_lookup_table(&account.data)?
+        else {
+            return Err(anyhow::anyhow!(
+                "Lookup table not initialized: {}",
+                lookup.account_key
+            ));
+        };
+
+        for (indexes, loaded) in [
+            (&lookup.writable_indexes, &mut writable),
+            (&lookup.readonly_indexes, &mut readonly),
+        ] {
+            for index in indexes {
+                let address = table.addresses.get(*index as usize).ok_or_else(|| {
+                    anyhow::anyhow!("Lookup table index out of range: {}", lookup.account_key)
+                })?;
+                loaded.push(address.clone());
+            }
+        }
+    }
+
+    keys.extend(writable);
+    keys.extend(readonly);
+    Ok(keys)
+}
+
+/// Mint and raw amount of an SPL token account
+fn token_account(data: &[u8]) -> Option<(String, u64)> {
+    let TokenAccountType::Account(account) = parse_token_v3(data, None).ok()? else {
+        return None;
+    };
+    Some((account.mint, account.token_amount.amount.parse().ok()?))
+}
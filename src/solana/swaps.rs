@@ -0,0 +1,287 @@
+use super::errors::JUPITER_V6_PROGRAM_ID;
+use super::idl::{sighash, EVENT_IX_TAG};
+use super::instructions::{token_account_mints, FlatInstruction};
+use super::{SolanaChainManager, SolanaTransfer, SolanaTransferKind};
+use serde::{Deserialize, Serialize};
+use solana_sdk::bs58;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+use std::collections::HashMap;
+
+pub(crate) const RAYDIUM_AMM_V4_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+pub(crate) const RAYDIUM_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub(crate) const RAYDIUM_CPMM_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub(crate) const ORCA_WHIRLPOOL_PROGRAM_ID: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+pub(crate) const METEORA_DLMM_PROGRAM_ID: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+
+/// Size of Jupiter's `SwapEvent`: amm, input mint, input amount, output mint, output amount
+const JUPITER_SWAP_EVENT_LEN: usize = 32 + 32 + 8 + 32 + 8;
+
+/// A single swap hop through an AMM pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaSwap {
+    /// AMM name, e.g. `Raydium AMM` or `Orca Whirlpool`
+    pub venue: String,
+    /// Program of the AMM that executed the hop
+    pub program_id: String,
+    /// `None` when the hop is only known from an aggregator event
+    pub pool: Option<String>,
+    /// Aggregator that routed the hop, e.g. `Jupiter`
+    pub aggregator: Option<String>,
+    pub instruction_index: usize,
+    pub inner_instruction_index: Option<usize>,
+    pub input_mint: Option<String>,
+    pub input_amount: u64,
+    pub output_mint: Option<String>,
+    pub output_amount: u64,
+    /// Output per unit of input in UI amounts, when both decimals are known
+    pub price: Option<f64>,
+}
+
+impl SolanaChainManager {
+    pub(super) fn extract_swaps(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        instructions: &[FlatInstruction],
+        transfers: &[SolanaTransfer],
+    ) -> Vec<SolanaSwap> {
+        let mints = token_account_mints(transaction);
+        let decimals = mint_decimals(transaction);
+
+        let aggregator = |outer_index: usize| {
+            instructions
+                .iter()
+                .find(|i| i.outer_index == outer_index && i.inner_index.is_none())
+                .filter(|i| i.program_id == JUPITER_V6_PROGRAM_ID)
+                .map(|_| "Jupiter".to_string())
+        };
+
+        // Hops executed directly by a known AMM, priced from the token transfers it makes
+        let mut swaps = Vec::new();
+        for instruction in instructions {
+            let Some((venue, pool_index, authority_index)) = amm_swap(instruction) else {
+                continue;
+            };
+
+            let calls = direct_calls(instructions, instruction);
+            let hop_transfers: Vec<&SolanaTransfer> = transfers
+                .iter()
+                .filter(|transfer| {
+                    transfer.instruction_index == instruction.outer_index
+                        && transfer
+                            .inner_instruction_index
+                            .is_some_and(|index| calls.contains(&index))
+                        && matches!(
+                            transfer.kind,
+                            SolanaTransferKind::Transfer
+                                | SolanaTransferKind::TransferChecked
+                                | SolanaTransferKind::TransferCheckedWithFee
+                        )
+                })
+                .take(2)
+                .collect();
+            let [first, second] = hop_transfers[..] else {
+                continue;
+            };
+
+            // The vaults are owned by the pool authority, which signs the output transfer
+            let pool_authority = instruction.accounts.get(authority_index);
+            let (input, output) = if first.authority.as_ref() == pool_authority {
+                (second, first)
+            } else {
+                (first, second)
+            };
+
+            let mint_of = |transfer: &SolanaTransfer| {
+                transfer.mint.clone().or_else(|| {
+                    [&transfer.source, &transfer.destination]
+                        .into_iter()
+                        .flatten()
+                        .find_map(|account| mints.get(account).cloned())
+                })
+            };
+
+            let input_mint = mint_of(input);
+            let output_mint = mint_of(output);
+            swaps.push(SolanaSwap {
+                venue: venue.to_string(),
+                program_id: instruction.program_id.clone(),
+                pool: instruction.accounts.get(pool_index).cloned(),
+                aggregator: aggregator(instruction.outer_index),
+                instruction_index: instruction.outer_index,
+                inner_instruction_index: instruction.inner_index,
+                price: price(
+                    &decimals,
+                    &input_mint,
+                    input.amount,
+                    &output_mint,
+                    output.amount,
+                ),
+                input_mint,
+                input_amount: input.amount,
+                output_mint,
+                output_amount: output.amount,
+            });
+        }
+
+        // Jupiter logs every hop as a `SwapEvent`, covering AMMs not decoded above
+        let swap_event = sighash("event", "SwapEvent");
+        for instruction in instructions {
+            if instruction.program_id != JUPITER_V6_PROGRAM_ID {
+                continue;
+            }
+            let Some(data) = instruction
+                .data
+                .strip_prefix(&EVENT_IX_TAG)
+                .and_then(|data| data.strip_prefix(&swap_event[..]))
+                .filter(|data| data.len() >= JUPITER_SWAP_EVENT_LEN)
+            else {
+                continue;
+            };
+
+            let pubkey = |offset: usize| bs58::encode(&data[offset..offset + 32]).into_string();
+            let amount =
+                |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+            let amm = pubkey(0);
+            let input_mint = Some(pubkey(32));
+            let input_amount = amount(64);
+            let output_mint = Some(pubkey(72));
+            let output_amount = amount(104);
+
+            let already_decoded = swaps.iter().any(|swap| {
+                swap.instruction_index == instruction.outer_index
+                    && swap.input_mint == input_mint
+                    && swap.input_amount == input_amount
+                    && swap.output_mint == output_mint
+                    && swap.output_amount == output_amount
+            });
+            if already_decoded {
+                continue;
+            }
+
+            swaps.push(SolanaSwap {
+                venue: venue_name(&amm).unwrap_or("Unknown").to_string(),
+                program_id: amm,
+                pool: None,
+                aggregator: Some("Jupiter".to_string()),
+                instruction_index: instruction.outer_index,
+                inner_instruction_index: instruction.inner_index,
+                price: price(
+                    &decimals,
+                    &input_mint,
+                    input_amount,
+                    &output_mint,
+                    output_amount,
+                ),
+                input_mint,
+                input_amount,
+                output_mint,
+                output_amount,
+            });
+        }
+
+        swaps.sort_by_key(|swap| (swap.instruction_index, swap.inner_instruction_index));
+        swaps
+    }
+}
+
+/// Inner indexes of the CPIs made by `instruction` itself, up to the next instruction at or
+/// above its stack height. Without stack heights the AMM is assumed not to nest further
+fn direct_calls(instructions: &[FlatInstruction], instruction: &FlatInstruction) -> Vec<usize> {
+    let height = instruction.stack_height;
+    instructions
+        .iter()
+        .filter(|inner| inner.outer_index == instruction.outer_index)
+        .skip_while(|inner| inner.inner_index != instruction.inner_index)
+        .skip(1)
+        .take_while(|inner| match (height, inner.stack_height) {
+            (Some(height), Some(inner_height)) => inner_height > height,
+            _ => inner.program_id != instruction.program_id,
+        })
+        .filter(|inner| match (height, inner.stack_height) {
+            (Some(height), Some(inner_height)) => inner_height == height + 1,
+            _ => true,
+        })
+        .filter_map(|inner| inner.inner_index)
+        .collect()
+}
+
+fn venue_name(program_id: &str) -> Option<&'static str> {
+    match program_id {
+        RAYDIUM_AMM_V4_PROGRAM_ID => Some("Raydium AMM"),
+        RAYDIUM_CLMM_PROGRAM_ID => Some("Raydium CLMM"),
+        RAYDIUM_CPMM_PROGRAM_ID => Some("Raydium CPMM"),
+        ORCA_WHIRLPOOL_PROGRAM_ID => Some("Orca Whirlpool"),
+        METEORA_DLMM_PROGRAM_ID => Some("Meteora DLMM"),
+        _ => None,
+    }
+}
+
+/// Venue, pool account index and vault authority account index of a swap instruction of a
+/// known AMM
+fn amm_swap(instruction: &FlatInstruction) -> Option<(&'static str, usize, usize)> {
+    let venue = venue_name(&instruction.program_id)?;
+    let data = &instruction.data;
+    let anchor_swap = |names: &[&str]| {
+        names
+            .iter()
+            .any(|name| data.starts_with(&sighash("global", name)))
+    };
+
+    let (pool_index, authority_index) = match instruction.program_id.as_str() {
+        // swapBaseIn / swapBaseOut
+        RAYDIUM_AMM_V4_PROGRAM_ID => matches!(data.first(), Some(9 | 11)).then_some((1, 2))?,
+        RAYDIUM_CLMM_PROGRAM_ID => anchor_swap(&["swap", "swap_v2"]).then_some((2, 2))?,
+        RAYDIUM_CPMM_PROGRAM_ID => {
+            anchor_swap(&["swap_base_input", "swap_base_output"]).then_some((3, 1))?
+        }
+        ORCA_WHIRLPOOL_PROGRAM_ID if anchor_swap(&["swap"]) => (2, 2),
+        ORCA_WHIRLPOOL_PROGRAM_ID => anchor_swap(&["swap_v2"]).then_some((4, 4))?,
+        METEORA_DLMM_PROGRAM_ID => anchor_swap(&[
+            "swap",
+            "swap2",
+            "swap_exact_out",
+            "swap_exact_out2",
+            "swap_with_price_impact",
+            "swap_with_price_impact2",
+        ])
+        .then_some((0, 0))?,
+        _ => return None,
+    };
+
+    Some((venue, pool_index, authority_index))
+}
+
+/// Decimals of every mint in the token balances
+fn mint_decimals(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> HashMap<String, u8> {
+    let mut decimals = HashMap::new();
+    if let Some(meta) = &transaction.transaction.meta {
+        for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+            if let OptionSerializer::Some(balances) = balances {
+                for balance in balances {
+                    decimals.insert(balance.mint.clone(), balance.ui_token_amount.decimals);
+                }
+            }
+        }
+    }
+    decimals
+}
+
+fn price(
+    decimals: &HashMap<String, u8>,
+    input_mint: &Option<String>,
+    input_amount: u64,
+    output_mint: &Option<String>,
+    output_amount: u64,
+) -> Option<f64> {
+    let input_decimals = *decimals.get(input_mint.as_ref()?)?;
+    let output_decimals = *decimals.get(output_mint.as_ref()?)?;
+    if input_amount == 0 {
+        return None;
+    }
+
+    let input = input_amount as f64 / 10f64.powi(input_decimals as i32);
+    let output = output_amount as f64 / 10f64.powi(output_decimals as i32);
+    Some(output / input)
+}
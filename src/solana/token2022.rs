@@ -0,0 +1,218 @@
+use super::instructions::TOKEN_2022_PROGRAM_ID;
+use super::{SolanaChainManager, SolanaTransfer, SolanaTransferKind};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_account_decoder::parse_token::{parse_token_v3, TokenAccountType};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{clock::DEFAULT_SLOTS_PER_EPOCH, pubkey::Pubkey};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+use std::{collections::BTreeSet, str::FromStr};
+
+/// Transfer fee parameters of a Token-2022 mint for one epoch range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferFeeSchedule {
+    /// First epoch the schedule applies to
+    pub epoch: u64,
+    pub transfer_fee_basis_points: u16,
+    pub maximum_fee: u64,
+}
+
+/// Token-2022 extensions of a mint that affect amounts or costs.
+///
+/// Read from the current mint account, so fees, rates and multipliers are today's and may
+/// differ from the ones in effect at the transaction's slot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintExtensions {
+    pub mint: String,
+    pub decimals: u8,
+    /// Names of every extension present on the mint
+    pub extensions: Vec<String>,
+    pub older_transfer_fee: Option<TransferFeeSchedule>,
+    pub newer_transfer_fee: Option<TransferFeeSchedule>,
+    /// Current interest rate in basis points; UI amounts accrue interest over raw amounts
+    pub interest_rate_bps: Option<i16>,
+    /// Multiplier applied to raw amounts when displaying them
+    pub scaled_ui_amount_multiplier: Option<String>,
+    pub transfer_hook_program_id: Option<String>,
+    pub permanent_delegate: Option<String>,
+}
+
+impl MintExtensions {
+    /// Fee withheld when transferring `amount` in `epoch`, or `None` without a fee config
+    pub fn transfer_fee(&self, epoch: u64, amount: u64) -> Option<u64> {
+        let schedule = match &self.newer_transfer_fee {
+            Some(newer) if epoch >= newer.epoch => newer,
+            _ => self.older_transfer_fee.as_ref()?,
+        };
+
+        let fee = (amount as u128 * schedule.transfer_fee_basis_points as u128).div_ceil(10_000);
+        Some((fee as u64).min(schedule.maximum_fee))
+    }
+}
+
+/// Token amount withheld by the Token-2022 TransferFee extension on a single transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenTransferFee {
+    pub mint: String,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    /// Amount debited from the source
+    pub amount: u64,
+    /// Part of `amount` withheld in the destination account instead of credited
+    pub fee: u64,
+    pub instruction_index: usize,
+}
+
+impl SolanaChainManager {
+    /// Fetch extension data for every Token-2022 mint in the transaction's token balances
+    pub(super) async fn fetch_mint_extensions(
+        &self,
+        client: &RpcClient,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<Vec<MintExtensions>> {
+        let mut mints = BTreeSet::new();
+
+        if let Some(meta) = &transaction.transaction.meta {
+            for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+                if let OptionSerializer::Some(balances) = balances {
+                    for balance in balances {
+                        if let OptionSerializer::Some(program_id) = &balance.program_id {
+                            if program_id == TOKEN_2022_PROGRAM_ID {
+                                mints.insert(balance.mint.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if mints.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mints: Vec<String> = mints.into_iter().collect();
+        let pubkeys = mints
+            .iter()
+            .map(|mint| Pubkey::from_str(mint))
+            .collect::<Result<Vec<_>, _>>()?;
+        let accounts = client.get_multiple_accounts(&pubkeys).await?;
+
+        Ok(mints
+            .into_iter()
+            .zip(accounts)
+            .filter_map(|(mint, account)| parse_mint_extensions(mint, &account?.data))
+            .collect())
+    }
+
+    /// Fill in the withheld fee of transfers whose mint charges a transfer fee
+    pub(super) fn apply_transfer_fees(
+        &self,
+        slot: u64,
+        transfers: &mut [SolanaTransfer],
+        mint_extensions: &[MintExtensions],
+    ) -> Vec<TokenTransferFee> {
+        let epoch = slot / DEFAULT_SLOTS_PER_EPOCH;
+        let mut fees = Vec::new();
+
+        for transfer in transfers.iter_mut() {
+            if !matches!(
+                transfer.kind,
+                SolanaTransferKind::Transfer
+                    | SolanaTransferKind::TransferChecked
+                    | SolanaTransferKind::TransferCheckedWithFee
+            ) {
+                continue;
+            }
+            let Some(mint) = transfer.mint.clone() else {
+                continue;
+            };
+
+            // `transferCheckedWithFee` states the fee explicitly, otherwise derive it from the mint
+            if transfer.fee.is_none() {
+                transfer.fee = mint_extensions
+                    .iter()
+                    .find(|extensions| extensions.mint == mint)
+                    .and_then(|extensions| extensions.transfer_fee(epoch, transfer.amount));
+            }
+
+            if let Some(fee) = transfer.fee.filter(|fee| *fee > 0) {
+                fees.push(TokenTransferFee {
+                    mint,
+                    source: transfer.source.clone(),
+                    destination: transfer.destination.clone(),
+                    amount: transfer.amount,
+                    fee,
+                    instruction_index: transfer.instruction_index,
+                });
+            }
+        }
+
+        fees
+    }
+}
+
+fn parse_mint_extensions(mint: String, data: &[u8]) -> Option<MintExtensions> {
+    let TokenAccountType::Mint(ui_mint) = parse_token_v3(data, None).ok()? else {
+        return None;
+    };
+
+    let mut extensions = MintExtensions {
+        mint,
+        decimals: ui_mint.decimals,
+        extensions: Vec::new(),
+        older_transfer_fee: None,
+        newer_transfer_fee: None,
+        interest_rate_bps: None,
+        scaled_ui_amount_multiplier: None,
+        transfer_hook_program_id: None,
+        permanent_delegate: None,
+    };
+
+    // Extensions serialize as `{ "extension": name, "state": {...} }`
+    let Ok(Value::Array(entries)) = serde_json::to_value(&ui_mint.extensions) else {
+        return Some(extensions);
+    };
+    for entry in entries {
+        let Some(name) = entry.get("extension").and_then(Value::as_str) else {
+            continue;
+        };
+        let state = entry.get("state").cloned().unwrap_or(Value::Null);
+        extensions.extensions.push(name.to_string());
+
+        match name {
+            "transferFeeConfig" => {
+                extensions.older_transfer_fee = transfer_fee_schedule(&state["olderTransferFee"]);
+                extensions.newer_transfer_fee = transfer_fee_schedule(&state["newerTransferFee"]);
+            }
+            "interestBearingConfig" => {
+                extensions.interest_rate_bps =
+                    state["currentRate"].as_i64().map(|rate| rate as i16);
+            }
+            "scaledUiAmountConfig" => {
+                extensions.scaled_ui_amount_multiplier =
+                    state["multiplier"].as_str().map(str::to_string);
+            }
+            "transferHook" => {
+                extensions.transfer_hook_program_id =
+                    state["programId"].as_str().map(str::to_string);
+            }
+            "permanentDelegate" => {
+                extensions.permanent_delegate = state["delegate"].as_str().map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+
+    Some(extensions)
+}
+
+fn transfer_fee_schedule(value: &Value) -> Option<TransferFeeSchedule> {
+    Some(TransferFeeSchedule {
+        epoch: value.get("epoch")?.as_u64()?,
+        transfer_fee_basis_points: value.get("transferFeeBasisPoints")?.as_u64()? as u16,
+        maximum_fee: value.get("maximumFee")?.as_u64()?,
+    })
+}
@@ -0,0 +1,173 @@
+use super::instructions::{
+    is_token_program, token_account_mints, FlatInstruction, SYSTEM_PROGRAM_ID,
+};
+use super::SolanaChainManager;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SolanaTransferKind {
+    /// System program lamport transfer
+    SystemTransfer,
+    Transfer,
+    TransferChecked,
+    /// Token-2022 `transferCheckedWithFee`
+    TransferCheckedWithFee,
+    MintTo,
+    Burn,
+}
+
+/// A SOL or SPL token movement decoded from a top-level or inner instruction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolanaTransfer {
+    pub kind: SolanaTransferKind,
+    pub program_id: String,
+    pub instruction_index: usize,
+    /// Position in the inner instructions of `instruction_index`, `None` for top-level
+    pub inner_instruction_index: Option<usize>,
+    /// Token mint, `None` for native SOL or when it cannot be resolved
+    pub mint: Option<String>,
+    /// Source account, `None` for mints
+    pub source: Option<String>,
+    /// Destination account, `None` for burns
+    pub destination: Option<String>,
+    /// Owner, delegate or mint authority that signed for the movement
+    pub authority: Option<String>,
+    pub amount: u64,
+    pub decimals: Option<u8>,
+    /// Token-2022 transfer fee withheld from `amount`
+    pub fee: Option<u64>,
+}
+
+impl SolanaChainManager {
+    pub(super) fn extract_transfers(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        instructions: &[FlatInstruction],
+    ) -> Vec<SolanaTransfer> {
+        self.decode_transfers(instructions, &token_account_mints(transaction))
+    }
+
+    /// Decode transfers given the mint of every known token account
+    pub(super) fn decode_transfers(
+        &self,
+        instructions: &[FlatInstruction],
+        mints: &HashMap<String, String>,
+    ) -> Vec<SolanaTransfer> {
+        instructions
+            .iter()
+            .filter_map(|instruction| {
+                let program_id = instruction.program_id.as_str();
+                let info = |key: &str| instruction.info_str(key).map(str::to_string);
+
+                let mut fee = None;
+                let (kind, mint, source, destination, authority, amount, decimals) =
+                    match instruction.parsed_type()? {
+                        "transfer" if program_id == SYSTEM_PROGRAM_ID => (
+                            SolanaTransferKind::SystemTransfer,
+                            None,
+                            info("source"),
+                            info("destination"),
+                            info("source"),
+                            instruction.info_u64("lamports")?,
+                            Some(9),
+                        ),
+                        "transfer" if is_token_program(program_id) => {
+                            let source = info("source");
+                            (
+                                SolanaTransferKind::Transfer,
+                                source.as_ref().and_then(|s| mints.get(s).cloned()),
+                                source,
+                                info("destination"),
+                                info("authority").or_else(|| info("multisigAuthority")),
+                                instruction.info_u64("amount")?,
+                                None,
+                            )
+                        }
+                        "transferChecked" if is_token_program(program_id) => {
+                            let (amount, decimals) = checked_amount(instruction)?;
+                            (
+                                SolanaTransferKind::TransferChecked,
+                                info("mint"),
+                                info("source"),
+                                info("destination"),
+                                info("authority").or_else(|| info("multisigAuthority")),
+                                amount,
+                                decimals,
+                            )
+                        }
+                        "transferCheckedWithFee" if is_token_program(program_id) => {
+                            let (amount, decimals) = checked_amount(instruction)?;
+                            fee = instruction
+                                .info()?
+                                .get("feeAmount")
+                                .and_then(|fee| fee.get("amount")?.as_str()?.parse().ok());
+                            (
+                                SolanaTransferKind::TransferCheckedWithFee,
+                                info("mint"),
+                                info("source"),
+                                info("destination"),
+                                info("authority").or_else(|| info("multisigAuthority")),
+                                amount,
+                                decimals,
+                            )
+                        }
+                        "mintTo" | "mintToChecked" if is_token_program(program_id) => {
+                            let (amount, decimals) = checked_amount(instruction)?;
+                            (
+                                SolanaTransferKind::MintTo,
+                                info("mint"),
+                                None,
+                                info("account"),
+                                info("mintAuthority").or_else(|| info("multisigMintAuthority")),
+                                amount,
+                                decimals,
+                            )
+                        }
+                        "burn" | "burnChecked" if is_token_program(program_id) => {
+                            let (amount, decimals) = checked_amount(instruction)?;
+                            (
+                                SolanaTransferKind::Burn,
+                                info("mint"),
+                                info("account"),
+                                None,
+                                info("authority").or_else(|| info("multisigAuthority")),
+                                amount,
+                                decimals,
+                            )
+                        }
+                        _ => return None,
+                    };
+
+                Some(SolanaTransfer {
+                    kind,
+                    program_id: instruction.program_id.clone(),
+                    instruction_index: instruction.outer_index,
+                    inner_instruction_index: instruction.inner_index,
+                    mint,
+                    source,
+                    destination,
+                    authority,
+                    amount,
+                    decimals,
+                    fee,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Amount and decimals of an instruction that exists in plain and `...Checked` form
+fn checked_amount(instruction: &FlatInstruction) -> Option<(u64, Option<u8>)> {
+    match instruction.info()?.get("tokenAmount") {
+        Some(token_amount) => Some((
+            token_amount.get("amount")?.as_str()?.parse().ok()?,
+            token_amount
+                .get("decimals")
+                .and_then(|d| d.as_u64())
+                .map(|d| d as u8),
+        )),
+        None => Some((instruction.info_u64("amount")?, None)),
+    }
+}
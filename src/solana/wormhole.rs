@@ -0,0 +1,203 @@
+use super::instructions::FlatInstruction;
+use super::{SolanaChainManager, SolanaTransfer, SolanaTransferKind};
+use crate::wormhole::{self, WormholeMessage, WormholeMessageKind, SOLANA_CHAIN_ID};
+use solana_sdk::bs58;
+
+pub(crate) const WORMHOLE_CORE_PROGRAM_ID: &str = "worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth";
+
+/// Core bridge instruction tags
+const POST_MESSAGE: u8 = 1;
+const POST_VAA: u8 = 2;
+const POST_MESSAGE_UNRELIABLE: u8 = 8;
+
+/// Account positions in `post_message`
+const EMITTER_ACCOUNT_INDEX: usize = 2;
+const FEE_COLLECTOR_ACCOUNT_INDEX: usize = 5;
+
+impl SolanaChainManager {
+    /// Messages posted to the core bridge and VAAs posted for redemption
+    pub(super) fn extract_wormhole_messages(
+        &self,
+        instructions: &[FlatInstruction],
+        transfers: &[SolanaTransfer],
+        logs: &[String],
+    ) -> Vec<WormholeMessage> {
+        // The core bridge logs the sequence of every message it posts, in execution order
+        let mut sequences = logs.iter().filter_map(|log| {
+            log.strip_prefix("Program log: Sequence: ")?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        });
+
+        let mut messages = Vec::new();
+        let mut previous_position = None;
+        for instruction in instructions
+            .iter()
+            .filter(|instruction| instruction.program_id == WORMHOLE_CORE_PROGRAM_ID)
+        {
+            let data = &instruction.data;
+            match data.first() {
+                Some(&POST_MESSAGE | &POST_MESSAGE_UNRELIABLE) => {
+                    let Some((nonce, payload, consistency_level)) = decode_post_message(&data[1..])
+                    else {
+                        continue;
+                    };
+                    let Some(emitter) = instruction
+                        .accounts
+                        .get(EMITTER_ACCOUNT_INDEX)
+                        .and_then(|emitter| bs58::decode(emitter).into_vec().ok())
+                    else {
+                        continue;
+                    };
+
+                    // The fee is transferred to the fee collector since the previous message,
+                    // either by the caller or by a top-level instruction before it
+                    let position =
+                        execution_position(instruction.outer_index, instruction.inner_index);
+                    let fee_collector = instruction.accounts.get(FEE_COLLECTOR_ACCOUNT_INDEX);
+                    let message_fee = transfers
+                        .iter()
+                        .filter(|transfer| {
+                            let transfer_position = execution_position(
+                                transfer.instruction_index,
+                                transfer.inner_instruction_index,
+                            );
+                            transfer.kind == SolanaTransferKind::SystemTransfer
+                                && transfer.destination.as_ref() == fee_collector
+                                && transfer_position < position
+                                && previous_position
+                                    .is_none_or(|previous| transfer_position > previous)
+                        })
+                        .map(|transfer| transfer.amount)
+                        .sum();
+                    previous_position = Some(position);
+
+                    let mut message = wormhole::message(
+                        WormholeMessageKind::Published,
+                        Some(WORMHOLE_CORE_PROGRAM_ID.to_string()),
+                        SOLANA_CHAIN_ID,
+                        &emitter,
+                        sequences.next(),
+                        nonce,
+                        consistency_level,
+                        payload,
+                    );
+                    message.message_fee = Some(message_fee);
+                    messages.push(message);
+                }
+                Some(&POST_VAA) => {
+                    if let Some(message) = decode_post_vaa(&data[1..]) {
+                        messages.push(message);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        messages
+    }
+}
+
+/// Sortable position of an instruction or transfer in execution order
+fn execution_position(outer_index: usize, inner_index: Option<usize>) -> (usize, isize) {
+    (outer_index, inner_index.map_or(-1, |index| index as isize))
+}
+
+/// Borsh `PostMessageData`: nonce, payload and consistency level
+fn decode_post_message(data: &[u8]) -> Option<(u32, &[u8], u8)> {
+    let nonce = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    let length = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+    let payload = data.get(8..8 + length)?;
+    let consistency_level = *data.get(8 + length)?;
+    Some((nonce, payload, consistency_level))
+}
+
+/// Borsh `PostVAAData`: the VAA body with its header, without the signatures
+fn decode_post_vaa(data: &[u8]) -> Option<WormholeMessage> {
+    let u32_at = |offset: usize| {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let guardian_set_index = u32_at(1)?;
+    let timestamp = u32_at(5)?;
+    let nonce = u32_at(9)?;
+    let emitter_chain = u16::from_le_bytes(data.get(13..15)?.try_into().ok()?);
+    let emitter_address = data.get(15..47)?;
+    let sequence = u64::from_le_bytes(data.get(47..55)?.try_into().ok()?);
+    let consistency_level = *data.get(55)?;
+    let length = u32_at(56)? as usize;
+    let payload = data.get(60..60 + length)?;
+
+    let mut message = wormhole::message(
+        WormholeMessageKind::Redeemed,
+        Some(WORMHOLE_CORE_PROGRAM_ID.to_string()),
+        emitter_chain,
+        emitter_address,
+        Some(sequence),
+        nonce,
+        consistency_level,
+        payload,
+    );
+    message.timestamp = Some(timestamp);
+    message.guardian_set_index = Some(guardian_set_index);
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Borsh `PostVAAData` of a 25 USDC Token Bridge transfer from Ethereum to Solana:
+    /// version 1, guardian set 4, timestamp 1700000000, nonce 0, emitter the Ethereum Token
+    /// Bridge, sequence 171234 and consistency level 1
+    const POST_VAA_DATA: &str = "010400000000f153650000000002000000000000000000000000003ee18b2214aff97000d974cf647e7c347e8fa585e29c02000000000001850000000100000000000000000000000000000000000000000000000000000000017d7840000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb480002c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d6100010000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn decodes_post_vaa() {
+        let message = decode_post_vaa(&hex::decode(POST_VAA_DATA).unwrap()).unwrap();
+
+        assert_eq!(message.kind, WormholeMessageKind::Redeemed);
+        assert_eq!(
+            message.core_contract.as_deref(),
+            Some(WORMHOLE_CORE_PROGRAM_ID)
+        );
+        assert_eq!(message.guardian_set_index, Some(4));
+        assert_eq!(message.timestamp, Some(1700000000));
+        assert_eq!(message.emitter_chain, 2);
+        assert_eq!(
+            message.emitter_address,
+            "0000000000000000000000003ee18b2214aff97000d974cf647e7c347e8fa585"
+        );
+        assert_eq!(message.sequence, Some(171234));
+        assert_eq!(message.consistency_level, 1);
+        // Signatures are verified on a separate account and not part of the instruction
+        assert_eq!(message.signatures, None);
+
+        let transfer = message.token_transfer.unwrap();
+        assert_eq!(transfer.amount, "25000000");
+        assert_eq!(transfer.recipient_chain, SOLANA_CHAIN_ID);
+    }
+
+    #[test]
+    fn rejects_truncated_post_vaa() {
+        let data = hex::decode(POST_VAA_DATA).unwrap();
+        assert!(decode_post_vaa(&data[..data.len() - 1]).is_none());
+        assert!(decode_post_vaa(&data[..50]).is_none());
+    }
+
+    #[test]
+    fn decodes_post_message() {
+        let mut data = 7u32.to_le_bytes().to_vec();
+        data.extend(3u32.to_le_bytes());
+        data.extend([0xaa, 0xbb, 0xcc]);
+        data.push(32);
+
+        assert_eq!(
+            decode_post_message(&data),
+            Some((7, &[0xaa, 0xbb, 0xcc][..], 32))
+        );
+        assert_eq!(decode_post_message(&data[..9]), None);
+    }
+}
@@ -0,0 +1,474 @@
+use super::instructions::{
+    full_account_keys, is_token_program, lamport_balances, FlatInstruction,
+    ASSOCIATED_TOKEN_PROGRAM_ID, NATIVE_MINT, SYSTEM_PROGRAM_ID,
+};
+use super::rent::rent_exempt_minimum;
+use super::{
+    RentEvent, RentEventKind, RentPayerSummary, SolanaChainManager, SolanaTransfer,
+    SolanaTransferKind,
+};
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// A wSOL account created and closed within the same transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemporaryWrappedSolAccount {
+    pub account: String,
+    pub owner: Option<String>,
+    /// Rent-exempt minimum deposited on creation and returned on close
+    pub rent_lamports: u64,
+    /// SOL wrapped by funding above the rent-exempt minimum and System transfers into the account
+    pub wrapped_lamports: u64,
+    /// wSOL received by token transfers, e.g. swap output
+    pub received_lamports: u64,
+    /// wSOL sent by token transfers, e.g. swap input
+    pub sent_lamports: u64,
+    /// wSOL unwrapped back to SOL on close, excluding the rent
+    pub unwrapped_lamports: u64,
+    pub created_instruction_index: usize,
+    pub closed_instruction_index: usize,
+}
+
+/// Native SOL and wSOL of one wallet netted into a single balance change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeSolChange {
+    pub owner: String,
+    pub sol_balance_change: i64,
+    /// Change of wSOL held in accounts that outlive the transaction
+    pub wsol_balance_change: i64,
+    pub net_balance_change: i64,
+    pub fee_paid: u64,
+    /// Rent still locked in accounts created by the wallet
+    pub net_rent: i64,
+    /// `net_balance_change` with fees and refundable rent added back
+    pub net_balance_change_excluding_costs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedSolSummary {
+    pub native_sol_changes: Vec<NativeSolChange>,
+    pub temporary_accounts: Vec<TemporaryWrappedSolAccount>,
+}
+
+impl SolanaChainManager {
+    pub(super) fn unify_wrapped_sol(
+        &self,
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        instructions: &[FlatInstruction],
+        transfers: &[SolanaTransfer],
+        rent_events: &[RentEvent],
+        rent_summary: &[RentPayerSummary],
+    ) -> WrappedSolSummary {
+        let native_accounts = native_mint_accounts(transaction, instructions);
+
+        // Temporary accounts have both their creation and their close in this transaction
+        let mut temporary_accounts = Vec::new();
+        for (account, owner) in &native_accounts {
+            let created = rent_events.iter().find(|event| {
+                &event.account == account && event.kind != RentEventKind::CloseAccount
+            });
+            let closed = rent_events.iter().find(|event| {
+                &event.account == account && event.kind == RentEventKind::CloseAccount
+            });
+            let (Some(created), Some(closed)) = (created, closed) else {
+                continue;
+            };
+
+            // Lamports funded above the rent-exempt minimum on creation are wrapped SOL
+            let funding = instructions.iter().find(|instruction| {
+                instruction.program_id == SYSTEM_PROGRAM_ID
+                    && matches!(
+                        instruction.parsed_type(),
+                        Some("createAccount" | "createAccountWithSeed")
+                    )
+                    && instruction.info_str("newAccount") == Some(account.as_str())
+            });
+            let (rent_lamports, mut wrapped_lamports) = match funding.and_then(|instruction| {
+                Some((
+                    instruction.info_u64("lamports")?,
+                    instruction.info_u64("space")?,
+                ))
+            }) {
+                Some((lamports, space)) => {
+                    let rent = rent_exempt_minimum(space).min(lamports);
+                    (rent, lamports - rent)
+                }
+                None => (created.lamports, 0),
+            };
+            let mut received_lamports = 0;
+            let mut sent_lamports = 0;
+            for transfer in transfers {
+                match transfer.kind {
+                    SolanaTransferKind::SystemTransfer
+                        if transfer.destination.as_ref() == Some(account) =>
+                    {
+                        wrapped_lamports += transfer.amount;
+                    }
+                    SolanaTransferKind::SystemTransfer => {}
+                    _ if transfer.destination.as_ref() == Some(account) => {
+                        received_lamports += transfer.amount;
+                    }
+                    _ if transfer.source.as_ref() == Some(account) => {
+                        sent_lamports += transfer.amount;
+                    }
+                    _ => {}
+                }
+            }
+
+            temporary_accounts.push(TemporaryWrappedSolAccount {
+                account: account.clone(),
+                owner: owner.clone(),
+                rent_lamports,
+                wrapped_lamports,
+                received_lamports,
+                sent_lamports,
+                unwrapped_lamports: (wrapped_lamports + received_lamports)
+                    .saturating_sub(sent_lamports),
+                created_instruction_index: created.instruction_index,
+                closed_instruction_index: closed.instruction_index,
+            });
+        }
+        temporary_accounts.sort_by_key(|account| account.created_instruction_index);
+
+        // wSOL held in persistent accounts counts as SOL of the owning wallet
+        let mut wsol_changes: BTreeMap<String, i64> = BTreeMap::new();
+        for (owner, change) in persistent_wsol_changes(transaction) {
+            if change != 0 {
+                *wsol_changes.entry(owner).or_default() += change;
+            }
+        }
+
+        // The fee payer comes first in the rent summary
+        let mut owners: Vec<String> = rent_summary
+            .iter()
+            .take(1)
+            .map(|s| s.payer.clone())
+            .collect();
+        for owner in wsol_changes
+            .keys()
+            .cloned()
+            .chain(temporary_accounts.iter().filter_map(|t| t.owner.clone()))
+        {
+            if !owners.contains(&owner) {
+                owners.push(owner);
+            }
+        }
+
+        let balances = lamport_balances(transaction);
+        let native_sol_changes = owners
+            .into_iter()
+            .map(|owner| {
+                let sol_balance_change = balances
+                    .get(&owner)
+                    .map(|(pre, post)| *post as i64 - *pre as i64)
+                    .unwrap_or(0);
+                let wsol_balance_change = wsol_changes.get(&owner).copied().unwrap_or(0);
+                let summary = rent_summary.iter().find(|summary| summary.payer == owner);
+                let fee_paid = summary.map(|s| s.fee_paid).unwrap_or(0);
+                let net_rent = summary.map(|s| s.net_rent).unwrap_or(0);
+                let net_balance_change = sol_balance_change + wsol_balance_change;
+
+                NativeSolChange {
+                    owner,
+                    sol_balance_change,
+                    wsol_balance_change,
+                    net_balance_change,
+                    fee_paid,
+                    net_rent,
+                    net_balance_change_excluding_costs: net_balance_change
+                        + fee_paid as i64
+                        + net_rent,
+                }
+            })
+            .collect();
+
+        WrappedSolSummary {
+            native_sol_changes,
+            temporary_accounts,
+        }
+    }
+}
+
+/// Native mint token accounts and their owners, from token balances and initializations
+fn native_mint_accounts(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    instructions: &[FlatInstruction],
+) -> HashMap<String, Option<String>> {
+    let mut accounts = HashMap::new();
+    let account_keys = full_account_keys(transaction);
+
+    if let Some(meta) = &transaction.transaction.meta {
+        for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+            if let OptionSerializer::Some(balances) = balances {
+                for balance in balances.iter().filter(|b| b.mint == NATIVE_MINT) {
+                    if let Some(account) = account_keys.get(balance.account_index as usize) {
+                        let owner = match &balance.owner {
+                            OptionSerializer::Some(owner) => Some(owner.clone()),
+                            _ => None,
+                        };
+                        accounts.insert(account.clone(), owner);
+                    }
+                }
+            }
+        }
+    }
+
+    // Accounts created and closed in the transaction never show up in token balances
+    for instruction in instructions {
+        let (account, owner) = match (instruction.program_id.as_str(), instruction.parsed_type()) {
+            (
+                program_id,
+                Some("initializeAccount" | "initializeAccount2" | "initializeAccount3"),
+            ) if is_token_program(program_id) => (
+                instruction.info_str("account"),
+                instruction.info_str("owner"),
+            ),
+            (ASSOCIATED_TOKEN_PROGRAM_ID, Some("create" | "createIdempotent")) => (
+                instruction.info_str("account"),
+                instruction.info_str("wallet"),
+            ),
+            _ => continue,
+        };
+        if instruction.info_str("mint") != Some(NATIVE_MINT) {
+            continue;
+        }
+        if let Some(account) = account {
+            let entry = accounts.entry(account.to_string()).or_insert(None);
+            if entry.is_none() {
+                *entry = owner.map(str::to_string);
+            }
+        }
+    }
+
+    accounts
+}
+
+/// wSOL balance change per owner for accounts present before and after the transaction
+fn persistent_wsol_changes(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<(String, i64)> {
+    let Some(meta) = &transaction.transaction.meta else {
+        return Vec::new();
+    };
+
+    // (owner, account index) -> (pre, post)
+    let mut amounts: HashMap<(String, u8), (u64, u64)> = HashMap::new();
+    for (balances, is_post) in [
+        (&meta.pre_token_balances, false),
+        (&meta.post_token_balances, true),
+    ] {
+        if let OptionSerializer::Some(balances) = balances {
+            for balance in balances.iter().filter(|b| b.mint == NATIVE_MINT) {
+                let OptionSerializer::Some(owner) = &balance.owner else {
+                    continue;
+                };
+                let amount = balance.ui_token_amount.amount.parse::<u64>().unwrap_or(0);
+                let entry = amounts
+                    .entry((owner.clone(), balance.account_index))
+                    .or_default();
+                if is_post {
+                    entry.1 = amount;
+                } else {
+                    entry.0 = amount;
+                }
+            }
+        }
+    }
+
+    amounts
+        .into_iter()
+        .map(|((owner, _), (pre, post))| (owner, post as i64 - pre as i64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::instructions::{flatten_instructions, TOKEN_PROGRAM_ID};
+    use super::super::SolanaConfig;
+    use super::*;
+    use serde_json::{json, Value};
+
+    const WALLET: &str = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi";
+    const WSOL_ACCOUNT: &str = "GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq";
+
+    /// createAccount of 2_039_280 lamports and 165 bytes owned by the Token program
+    const CREATE_ACCOUNT: &str =
+        "11119os1e9qSs2u7TsThXqkBSRVFxhmYaFKFZ1waB2X7armDmvK3p5GmLdUxYdg3h7QSrL";
+    /// createAccount funding 502_039_280 lamports, 500_000_000 above the rent-exempt minimum
+    const CREATE_FUNDED_ACCOUNT: &str =
+        "11119pi5n6izZWVA1FYGvPh1W7mXU62CikAgAAyfGhFS2jNt7mqhLARHgEg6s25qtvyLJt";
+    /// initializeAccount, followed by the account and the native mint
+    const INITIALIZE_ACCOUNT: &str = "6MCcy5Vzot9a4QaM2XTXFmrD9k3o25oxXGKqWYxjR3Ukg";
+    /// System transfers of 50_000_000 and 2_000_000 lamports
+    const TRANSFER_50M: &str = "3Bxs4NRZ15a54oAf";
+    const TRANSFER_2M: &str = "3Bxs4NMRjdEwjxAj";
+    const SYNC_NATIVE: &str = "J";
+    const CLOSE_ACCOUNT: &str = "A";
+
+    /// A transaction of `WALLET` over the wSOL account with the given System (2) and Token
+    /// (3) program instructions
+    fn transaction(
+        instructions: &[(u8, &[u8], &str)],
+        fee: u64,
+        balances: (&[u64], &[u64]),
+        token_balances: (Value, Value),
+    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        let instructions: Vec<Value> = instructions
+            .iter()
+            .map(|(program, accounts, data)| {
+                json!({ "programIdIndex": program, "accounts": accounts, "data": data })
+            })
+            .collect();
+        serde_json::from_value(json!({
+            "slot": 300000000,
+            "blockTime": 1700000000,
+            "transaction": {
+                "signatures": ["99eUso3aSbE9tqGSTXzo3TLfKb9RkMTURrHKQ1K7Zh3BbeqPevr5E1iCbpTjqHuTFLtfxTTD5ekfVuZFzQyEQf8"],
+                "message": {
+                    "header": {
+                        "numRequiredSignatures": 2,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 3
+                    },
+                    "accountKeys": [WALLET, WSOL_ACCOUNT, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID, NATIVE_MINT],
+                    "recentBlockhash": "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8",
+                    "instructions": instructions
+                }
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": fee,
+                "preBalances": balances.0,
+                "postBalances": balances.1,
+                "innerInstructions": [],
+                "logMessages": [],
+                "preTokenBalances": token_balances.0,
+                "postTokenBalances": token_balances.1,
+                "rewards": []
+            }
+        }))
+        .unwrap()
+    }
+
+    fn wsol_balance(amount: u64) -> Value {
+        json!([{
+            "accountIndex": 1,
+            "mint": NATIVE_MINT,
+            "owner": WALLET,
+            "uiTokenAmount": {
+                "amount": amount.to_string(),
+                "decimals": 9,
+                "uiAmount": null,
+                "uiAmountString": ""
+            }
+        }])
+    }
+
+    fn unify(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> WrappedSolSummary {
+        let manager = SolanaChainManager::new(&SolanaConfig {
+            solana_mainnet_rpc_url: "http://localhost:8899".to_string(),
+            solana_tip_accounts: Vec::new(),
+            solana_idl_dir: None,
+            solana_history_concurrency: 1,
+            solana_unify_wsol: true,
+            solana_wormhole_vaa_api_url: None,
+        });
+        let instructions = flatten_instructions(transaction);
+        let rent_events = manager.extract_rent_events(transaction, &instructions);
+        let rent_summary = manager.summarize_rent(transaction, &rent_events);
+        let transfers = manager.extract_transfers(transaction, &instructions);
+        manager.unify_wrapped_sol(
+            transaction,
+            &instructions,
+            &transfers,
+            &rent_events,
+            &rent_summary,
+        )
+    }
+
+    #[test]
+    fn nets_wrap_and_unwrap_through_temporary_account() {
+        let transaction = transaction(
+            &[
+                (2, &[0, 1], CREATE_ACCOUNT),
+                (3, &[1, 4], INITIALIZE_ACCOUNT),
+                (2, &[0, 1], TRANSFER_50M),
+                (3, &[1], SYNC_NATIVE),
+                (3, &[1, 0, 0], CLOSE_ACCOUNT),
+            ],
+            10_000,
+            (&[1_000_000_000, 0, 1, 1, 1], &[999_990_000, 0, 1, 1, 1]),
+            (json!([]), json!([])),
+        );
+        let summary = unify(&transaction);
+
+        let account = &summary.temporary_accounts[0];
+        assert_eq!(account.account, WSOL_ACCOUNT);
+        assert_eq!(account.owner.as_deref(), Some(WALLET));
+        assert_eq!(account.rent_lamports, 2_039_280);
+        assert_eq!(account.wrapped_lamports, 50_000_000);
+        assert_eq!(account.unwrapped_lamports, 50_000_000);
+        assert_eq!(
+            (
+                account.created_instruction_index,
+                account.closed_instruction_index
+            ),
+            (0, 4)
+        );
+
+        let change = &summary.native_sol_changes[0];
+        assert_eq!(change.owner, WALLET);
+        assert_eq!(change.net_balance_change, -10_000);
+        assert_eq!(change.net_rent, 0);
+        assert_eq!(change.net_balance_change_excluding_costs, 0);
+    }
+
+    #[test]
+    fn splits_creation_funding_into_rent_and_wrapped_sol() {
+        let transaction = transaction(
+            &[
+                (2, &[0, 1], CREATE_FUNDED_ACCOUNT),
+                (3, &[1, 4], INITIALIZE_ACCOUNT),
+                (3, &[1], SYNC_NATIVE),
+                (3, &[1, 0, 0], CLOSE_ACCOUNT),
+            ],
+            10_000,
+            (&[1_000_000_000, 0, 1, 1, 1], &[999_990_000, 0, 1, 1, 1]),
+            (json!([]), json!([])),
+        );
+        let summary = unify(&transaction);
+
+        let account = &summary.temporary_accounts[0];
+        assert_eq!(account.rent_lamports, 2_039_280);
+        assert_eq!(account.wrapped_lamports, 500_000_000);
+        assert_eq!(account.unwrapped_lamports, 500_000_000);
+        assert_eq!(account.closed_instruction_index, 3);
+    }
+
+    #[test]
+    fn counts_persistent_wsol_as_the_owners_sol() {
+        let transaction = transaction(
+            &[(2, &[0, 1], TRANSFER_2M), (3, &[1], SYNC_NATIVE)],
+            5_000,
+            (
+                &[1_000_000_000, 3_039_280, 1, 1, 1],
+                &[997_995_000, 5_039_280, 1, 1, 1],
+            ),
+            (wsol_balance(1_000_000), wsol_balance(3_000_000)),
+        );
+        let summary = unify(&transaction);
+
+        assert!(summary.temporary_accounts.is_empty());
+        let change = &summary.native_sol_changes[0];
+        assert_eq!(change.owner, WALLET);
+        assert_eq!(change.sol_balance_change, -2_005_000);
+        assert_eq!(change.wsol_balance_change, 2_000_000);
+        assert_eq!(change.net_balance_change, -5_000);
+        assert_eq!(change.fee_paid, 5_000);
+        assert_eq!(change.net_balance_change_excluding_costs, 0);
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Wormhole chain id of Solana
+pub const SOLANA_CHAIN_ID: u16 = 1;
+
+/// Size of a guardian signature: guardian index, r, s and v
+const GUARDIAN_SIGNATURE_LEN: usize = 66;
+/// Size of a VAA body without its payload
+const VAA_BODY_HEADER_LEN: usize = 4 + 4 + 2 + 32 + 8 + 1;
+/// Size of a Token Bridge transfer payload without the `fee` or `from_address` field
+const TOKEN_TRANSFER_LEN: usize = 1 + 32 + 32 + 2 + 32 + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WormholeMessageKind {
+    /// Message published through the core bridge by this transaction
+    Published,
+    /// Signed VAA submitted to this transaction for redemption
+    Redeemed,
+}
+
+/// A Wormhole message published or redeemed by a transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WormholeMessage {
+    pub kind: WormholeMessageKind,
+    /// Core bridge contract or program the message was published to or posted on
+    pub core_contract: Option<String>,
+    pub emitter_chain: u16,
+    /// 32-byte universal address, hex encoded without `0x`
+    pub emitter_address: String,
+    /// `None` when the sequence cannot be recovered, e.g. from truncated Solana logs
+    pub sequence: Option<u64>,
+    pub nonce: u32,
+    pub consistency_level: u8,
+    /// VAA fields, only known for redeemed messages
+    pub timestamp: Option<u32>,
+    pub guardian_set_index: Option<u32>,
+    pub signatures: Option<usize>,
+    /// Hex encoded payload
+    pub payload: String,
+    /// `token_transfer`, `asset_meta` or `token_transfer_with_payload` for Token Bridge payloads
+    pub payload_type: Option<String>,
+    pub token_transfer: Option<WormholeTokenTransfer>,
+    /// Core bridge fee paid for publishing, in the smallest native unit
+    pub message_fee: Option<u64>,
+    /// Base64 signed VAA, when looked up from the configured VAA API
+    pub signed_vaa: Option<String>,
+}
+
+/// Token Bridge transfer payload, type 1 or 3
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WormholeTokenTransfer {
+    /// Amount normalized to at most 8 decimals
+    pub amount: String,
+    pub token_address: String,
+    pub token_chain: u16,
+    pub recipient: String,
+    pub recipient_chain: u16,
+    /// Relayer fee of type 1 transfers
+    pub fee: Option<String>,
+    /// Sender of type 3 transfers
+    pub from_address: Option<String>,
+    /// Hex encoded application payload of type 3 transfers
+    pub transfer_payload: Option<String>,
+}
+
+/// Wormhole chain id of a chain key
+pub fn chain_id(chain: &str) -> Option<u16> {
+    match chain {
+        "solana" | "mainnet" => Some(SOLANA_CHAIN_ID),
+        "ethereum" => Some(2),
+        "polygon" => Some(5),
+        "avalanche" => Some(6),
+        "arbitrum" => Some(23),
+        "optimism" => Some(24),
+        "base" => Some(30),
+        "unichain" => Some(44),
+        _ => None,
+    }
+}
+
+/// Core bridge contract of an EVM chain key, the only emitter of genuine
+/// `LogMessagePublished` events
+pub fn core_bridge(chain: &str) -> Option<&'static str> {
+    match chain {
+        "ethereum" => Some("0x98f3c9e6e3face36baad05fe09d375ef1464288b"),
+        "polygon" => Some("0x7a4b5a56256163f07b2c80a7ca55abe66c4ec4d7"),
+        "avalanche" => Some("0x54a8e5f9c4cba08f9943965859f6c34eaf03e26c"),
+        "arbitrum" => Some("0xa5f208e072434bc67592e4c49c1b991ba79bca46"),
+        "optimism" => Some("0xee91c335eab126df5fdb3797ea9d6ad93aec9722"),
+        "base" => Some("0xbebdb6c8ddc678ffa9f8748f85c815c556dd8ac6"),
+        "unichain" => Some("0xca1d5a146b03f6303baf59e5ad5615ae0b9d146d"),
+        _ => None,
+    }
+}
+
+/// Chain key of a Wormhole chain id, `solana` for Solana mainnet
+pub fn chain_key(chain_id: u16) -> Option<&'static str> {
+    match chain_id {
+        SOLANA_CHAIN_ID => Some("solana"),
+        2 => Some("ethereum"),
+        5 => Some("polygon"),
+        6 => Some("avalanche"),
+        23 => Some("arbitrum"),
+        24 => Some("optimism"),
+        30 => Some("base"),
+        44 => Some("unichain"),
+        _ => None,
+    }
+}
+
+/// Build a message from its body fields, decoding Token Bridge payloads
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn message(
+    kind: WormholeMessageKind,
+    core_contract: Option<String>,
+    emitter_chain: u16,
+    emitter_address: &[u8],
+    sequence: Option<u64>,
+    nonce: u32,
+    consistency_level: u8,
+    payload: &[u8],
+) -> WormholeMessage {
+    let (payload_type, token_transfer) = decode_payload(payload);
+    WormholeMessage {
+        kind,
+        core_contract,
+        emitter_chain,
+        emitter_address: universal_address(emitter_address),
+        sequence,
+        nonce,
+        consistency_level,
+        timestamp: None,
+        guardian_set_index: None,
+        signatures: None,
+        payload: hex::encode(payload),
+        payload_type,
+        token_transfer,
+        message_fee: None,
+        signed_vaa: None,
+    }
+}
+
+/// Parse a signed VAA: header, guardian signatures and body, all big-endian
+pub fn parse_vaa(vaa: &[u8]) -> Option<WormholeMessage> {
+    if vaa.len() < 6 || vaa[0] != 1 {
+        return None;
+    }
+    let guardian_set_index = u32::from_be_bytes(vaa[1..5].try_into().ok()?);
+    let signatures = vaa[5] as usize;
+    let body = vaa.get(6 + signatures * GUARDIAN_SIGNATURE_LEN..)?;
+    if body.len() < VAA_BODY_HEADER_LEN {
+        return None;
+    }
+
+    let mut message = message(
+        WormholeMessageKind::Redeemed,
+        None,
+        u16::from_be_bytes(body[8..10].try_into().ok()?),
+        &body[10..42],
+        Some(u64::from_be_bytes(body[42..50].try_into().ok()?)),
+        u32::from_be_bytes(body[4..8].try_into().ok()?),
+        body[50],
+        &body[VAA_BODY_HEADER_LEN..],
+    );
+    message.timestamp = Some(u32::from_be_bytes(body[0..4].try_into().ok()?));
+    message.guardian_set_index = Some(guardian_set_index);
+    message.signatures = Some(signatures);
+    Some(message)
+}
+
+/// Payload type and transfer details of Token Bridge payloads
+fn decode_payload(payload: &[u8]) -> (Option<String>, Option<WormholeTokenTransfer>) {
+    let word = |offset: usize| hex::encode(&payload[offset..offset + 32]);
+    let amount = |offset: usize| U256::from_big_endian(&payload[offset..offset + 32]).to_string();
+    let chain = |offset: usize| u16::from_be_bytes([payload[offset], payload[offset + 1]]);
+
+    match payload.first() {
+        Some(1) if payload.len() == TOKEN_TRANSFER_LEN + 32 => (
+            Some("token_transfer".to_string()),
+            Some(WormholeTokenTransfer {
+                amount: amount(1),
+                token_address: word(33),
+                token_chain: chain(65),
+                recipient: word(67),
+                recipient_chain: chain(99),
+                fee: Some(amount(101)),
+                from_address: None,
+                transfer_payload: None,
+            }),
+        ),
+        // decimals, symbol and name of the attested token
+        Some(2) if payload.len() == 1 + 32 + 2 + 1 + 32 + 32 => {
+            (Some("asset_meta".to_string()), None)
+        }
+        Some(3) if payload.len() >= TOKEN_TRANSFER_LEN + 32 => (
+            Some("token_transfer_with_payload".to_string()),
+            Some(WormholeTokenTransfer {
+                amount: amount(1),
+                token_address: word(33),
+                token_chain: chain(65),
+                recipient: word(67),
+                recipient_chain: chain(99),
+                fee: None,
+                from_address: Some(word(101)),
+                transfer_payload: Some(hex::encode(&payload[TOKEN_TRANSFER_LEN + 32..])),
+            }),
+        ),
+        _ => (None, None),
+    }
+}
+
+/// Left-pad an address to the 32-byte Wormhole universal address
+fn universal_address(address: &[u8]) -> String {
+    let mut universal = [0u8; 32];
+    let len = address.len().min(32);
+    universal[32 - len..].copy_from_slice(&address[address.len() - len..]);
+    hex::encode(universal)
+}
+
+/// Fill `signed_vaa` of published messages from a guardian compatible
+/// `/v1/signed_vaa/{chain}/{emitter}/{sequence}` endpoint
+pub(crate) async fn fetch_signed_vaas(
+    client: &reqwest::Client,
+    api_url: &str,
+    messages: &mut [WormholeMessage],
+) {
+    for message in messages
+        .iter_mut()
+        .filter(|message| message.kind == WormholeMessageKind::Published)
+    {
+        let Some(sequence) = message.sequence else {
+            continue;
+        };
+        let url = format!(
+            "{}/v1/signed_vaa/{}/{}/{}",
+            api_url.trim_end_matches('/'),
+            message.emitter_chain,
+            message.emitter_address,
+            sequence
+        );
+        // The VAA is missing until the guardians have signed it, which is not an error
+        match fetch_signed_vaa(client, &url).await {
+            Ok(vaa) => message.signed_vaa = vaa,
+            Err(e) => eprintln!("Error fetching signed VAA: {}", e),
+        }
+    }
+}
+
+async fn fetch_signed_vaa(client: &reqwest::Client, url: &str) -> Result<Option<String>> {
+    let response = client.get(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body: Value = response.error_for_status()?.json().await?;
+
+    // Guardians answer `vaaBytes`, Wormholescan nests the VAA under `data`
+    let vaa = body["vaaBytes"]
+        .as_str()
+        .or_else(|| body["data"]["vaa"].as_str())
+        .ok_or_else(|| anyhow::anyhow!("No VAA in response from {}", url))?;
+    BASE64.decode(vaa)?;
+    Ok(Some(vaa.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Guardian set 4 VAA of a 25 USDC Token Bridge transfer from Ethereum to Solana, with a
+    /// single placeholder signature: timestamp 1700000000, nonce 0, emitter the Ethereum
+    /// Token Bridge, sequence 171234 and consistency level 1
+    const TRANSFER_VAA: &str = "0100000004010011111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111016553f1000000000000020000000000000000000000003ee18b2214aff97000d974cf647e7c347e8fa5850000000000029ce2010100000000000000000000000000000000000000000000000000000000017d7840000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb480002c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d6100010000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn parses_token_transfer_vaa() {
+        let message = parse_vaa(&hex::decode(TRANSFER_VAA).unwrap()).unwrap();
+
+        assert_eq!(message.kind, WormholeMessageKind::Redeemed);
+        assert_eq!(message.guardian_set_index, Some(4));
+        assert_eq!(message.signatures, Some(1));
+        assert_eq!(message.timestamp, Some(1700000000));
+        assert_eq!(message.nonce, 0);
+        assert_eq!(message.emitter_chain, 2);
+        assert_eq!(
+            message.emitter_address,
+            "0000000000000000000000003ee18b2214aff97000d974cf647e7c347e8fa585"
+        );
+        assert_eq!(message.sequence, Some(171234));
+        assert_eq!(message.consistency_level, 1);
+        assert_eq!(message.payload_type.as_deref(), Some("token_transfer"));
+
+        let transfer = message.token_transfer.unwrap();
+        assert_eq!(transfer.amount, "25000000");
+        assert_eq!(
+            transfer.token_address,
+            "000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+        );
+        assert_eq!(transfer.token_chain, 2);
+        assert_eq!(
+            transfer.recipient,
+            "c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d61"
+        );
+        assert_eq!(transfer.recipient_chain, SOLANA_CHAIN_ID);
+        assert_eq!(transfer.fee.as_deref(), Some("0"));
+        assert_eq!(transfer.from_address, None);
+    }
+
+    #[test]
+    fn decodes_transfer_with_payload() {
+        // Same transfer as type 3, the fee replaced by the sender and followed by a payload
+        let vaa = hex::decode(TRANSFER_VAA).unwrap();
+        let payload_start = vaa.len() - (TOKEN_TRANSFER_LEN + 32);
+        let mut payload = vaa[payload_start..vaa.len() - 32].to_vec();
+        payload[0] = 3;
+        payload.extend([0x22; 32]);
+        payload.extend([0xca, 0xfe]);
+
+        let (payload_type, transfer) = decode_payload(&payload);
+
+        assert_eq!(payload_type.as_deref(), Some("token_transfer_with_payload"));
+        let transfer = transfer.unwrap();
+        assert_eq!(transfer.fee, None);
+        assert_eq!(transfer.from_address, Some("22".repeat(32)));
+        assert_eq!(transfer.transfer_payload.as_deref(), Some("cafe"));
+    }
+
+    #[test]
+    fn rejects_malformed_vaas() {
+        let vaa = hex::decode(TRANSFER_VAA).unwrap();
+
+        let mut wrong_version = vaa.clone();
+        wrong_version[0] = 2;
+        assert!(parse_vaa(&wrong_version).is_none());
+        // Cut inside the body header
+        assert!(parse_vaa(&vaa[..6 + GUARDIAN_SIGNATURE_LEN + 20]).is_none());
+        // More signatures announced than present
+        let mut missing_signatures = vaa.clone();
+        missing_signatures[5] = 13;
+        assert!(parse_vaa(&missing_signatures).is_none());
+    }
+
+    #[test]
+    fn maps_chain_keys_both_ways() {
+        assert_eq!(chain_id("mainnet"), Some(SOLANA_CHAIN_ID));
+        for chain in ["ethereum", "arbitrum", "base"] {
+            assert_eq!(chain_id(chain).and_then(chain_key), Some(chain));
+            assert!(core_bridge(chain).is_some());
+        }
+        assert_eq!(core_bridge("solana"), None);
+    }
+}